@@ -1,3 +1,7 @@
 fn main() {
-    println!("cargo:rustc-link-arg=-Tlinkall.x");
+    // The linker script comes with esp-hal, host builds with the `sim`
+    // feature link as usual.
+    if std::env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("none") {
+        println!("cargo:rustc-link-arg=-Tlinkall.x");
+    }
 }
@@ -0,0 +1,214 @@
+//! Access port addressing for ADIv5 and ADIv6 debug ports.
+
+use heapless::Vec;
+use log::info;
+
+use crate::io::SwdIo;
+use crate::jep106::{self, Jep106};
+use crate::registers::ap::{APClass, APRegister, APType, Idr};
+use crate::registers::dp::{BasePtr0, BasePtr1, Dpidr};
+use crate::swd::{RequestError, Swd};
+
+/// An access port as addressed through the DP SELECT register.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ApAddress {
+    /// ADIv5 APSEL, registers are banked through SELECT.APBANKSEL.
+    Adiv5(u8),
+    /// ADIv6 base address of the 4 KiB register window of the AP, selected
+    /// through SELECT and SELECT1.
+    Adiv6(u64),
+}
+
+impl ApAddress {
+    /// Offset of `Reg` for this kind of AP.
+    pub fn register<Reg: APRegister>(&self) -> u16 {
+        match self {
+            ApAddress::Adiv5(_) => Reg::ADDRESS.into(),
+            ApAddress::Adiv6(_) => Reg::ADIV6_ADDRESS,
+        }
+    }
+}
+
+impl From<u8> for ApAddress {
+    fn from(apsel: u8) -> Self {
+        ApAddress::Adiv5(apsel)
+    }
+}
+
+/// Most APs reported by `Swd::enumerate_aps`.
+pub const MAX_APS: usize = 32;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ApKind {
+    /// MEM-AP on the bus given by IDR.TYPE.
+    MemAp(APType),
+    /// ARM JTAG-AP.
+    JtagAp,
+    /// COM-AP.
+    ComAp,
+    /// Nordic CTRL-AP, for mass erase and readback protection.
+    NordicCtrlAp,
+    /// Vendor specific AP without an ADI class, such as the ST ones.
+    Vendor(Jep106),
+    Unknown,
+}
+
+/// An AP found by `Swd::enumerate_aps`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ApInfo {
+    pub address: ApAddress,
+    pub idr: Idr,
+}
+
+impl ApInfo {
+    pub fn designer(&self) -> Jep106 {
+        self.idr.designer()
+    }
+
+    pub fn kind(&self) -> ApKind {
+        match (self.designer(), self.idr.class()) {
+            (_, APClass::MemAp) => ApKind::MemAp(self.idr.ap_type()),
+            (_, APClass::ComAp) => ApKind::ComAp,
+            (jep106::ARM, APClass::NoClass) if self.idr.is_jtag_connection() => ApKind::JtagAp,
+            (jep106::NORDIC, APClass::NoClass) => ApKind::NordicCtrlAp,
+            (jep106::ARM, _) => ApKind::Unknown,
+            (designer, _) => ApKind::Vendor(designer),
+        }
+    }
+}
+
+impl<Io: SwdIo> Swd<Io> {
+    /// Find the ADIv5 APs by reading IDR of APSEL 0 to 255.
+    ///
+    /// APs are numbered from 0 without gaps, so the scan stops at the first
+    /// zero IDR unless `past_gaps` is set. At most `MAX_APS` are returned.
+    pub async fn enumerate_aps(
+        &mut self,
+        past_gaps: bool,
+    ) -> Result<Vec<ApInfo, MAX_APS>, RequestError> {
+        let mut aps = Vec::new();
+        for apsel in 0..=u8::MAX {
+            let idr = match self.read_ap_register::<Idr>(apsel).await {
+                Ok(idr) => idr,
+                // Some DPs fault accesses to APs that are not implemented.
+                Err(RequestError::Sticky(_)) => Idr::default(),
+                Err(err) => return Err(err),
+            };
+            if idr == Idr::default() {
+                if past_gaps {
+                    continue;
+                }
+                break;
+            }
+            let ap = ApInfo {
+                address: ApAddress::Adiv5(apsel),
+                idr,
+            };
+            info!("AP {}: {:?} {:x?}", apsel, ap.kind(), idr);
+            if aps.push(ap).is_err() {
+                break;
+            }
+        }
+        Ok(aps)
+    }
+
+    /// Address of the root component of an ADIv6 DP from BASEPTR0/1, the
+    /// ROM table that lists its APs. `None` for older DPs or no root table.
+    pub async fn base_pointer(&mut self) -> Result<Option<u64>, RequestError> {
+        // BASEPTR0 shares address 0x0 with DPIDR, which DPv2 and older do not
+        // bank, so only read it from DPv3.
+        if self.read_dp_register::<Dpidr>().await?.version() < 3 {
+            return Ok(None);
+        }
+        let baseptr0 = self.read_dp_register::<BasePtr0>().await?;
+        if !baseptr0.valid() {
+            return Ok(None);
+        }
+        let baseptr1 = self.read_dp_register::<BasePtr1>().await?;
+        Ok(Some(
+            (u64::from(baseptr1.ptr()) << 32) | u64::from(baseptr0.ptr() << 12),
+        ))
+    }
+}
+
+#[cfg(all(test, feature = "sim"))]
+mod tests {
+    use super::*;
+    use crate::sim::{block_on, MemApSim, Memory, SimIo, Target, IDCODE};
+
+    /// DPv3 DPIDR of an ADIv6 DP.
+    const DPIDR_V3: u32 = 0x4ba0_3477;
+    const BASEPTR: u64 = 0x1_0000_2000;
+
+    #[test]
+    fn enumerate_aps() {
+        let mut empty = MemApSim::new(0, Memory::new());
+        empty.idr = 0;
+        let mut ctrl_ap = MemApSim::new(0, Memory::new());
+        ctrl_ap.idr = 0x0288_0000;
+        let target = Target::new(IDCODE)
+            .with_ap(MemApSim::new(0xe00ff003, Memory::new()))
+            .with_ap(empty)
+            .with_ap(ctrl_ap);
+        let mut swd = Swd::new(SimIo::new(target));
+        block_on(async {
+            swd.reset().await.unwrap();
+            let aps = swd.enumerate_aps(false).await.unwrap();
+            assert_eq!(aps.len(), 1);
+            assert_eq!(aps[0].address, ApAddress::Adiv5(0));
+            assert_eq!(aps[0].kind(), ApKind::MemAp(APType::AHB));
+
+            let aps = swd.enumerate_aps(true).await.unwrap();
+            assert_eq!(aps.len(), 2);
+            assert_eq!(aps[1].address, ApAddress::Adiv5(2));
+            assert_eq!(aps[1].designer(), jep106::NORDIC);
+            assert_eq!(aps[1].kind(), ApKind::NordicCtrlAp);
+        });
+    }
+
+    #[test]
+    fn base_pointer() {
+        let mut swd = Swd::new(SimIo::new(Target::new(DPIDR_V3).with_baseptr(BASEPTR)));
+        block_on(async {
+            swd.reset().await.unwrap();
+            assert_eq!(swd.base_pointer().await, Ok(Some(BASEPTR)));
+            let dpidr = swd.read_dp_register::<Dpidr>().await.unwrap();
+            assert_eq!(u32::from(dpidr), DPIDR_V3);
+
+            // DPIDR is read from bank 0 whatever bank was left selected.
+            swd.select_dp_bank(2).await.unwrap();
+            let dpidr = swd.read_dp_register::<Dpidr>().await.unwrap();
+            assert_eq!(u32::from(dpidr), DPIDR_V3);
+        });
+
+        let mut swd = Swd::new(SimIo::new(Target::new(IDCODE)));
+        block_on(async {
+            swd.reset().await.unwrap();
+            assert_eq!(swd.base_pointer().await, Ok(None));
+        });
+    }
+
+    #[test]
+    fn adiv6_memap() {
+        let target = Target::new(DPIDR_V3)
+            .with_baseptr(BASEPTR)
+            .with_ap(MemApSim::new(0, Memory::new()))
+            .with_ap(MemApSim::new(0xe00ff003, Memory::new()));
+        let mut swd = Swd::new(SimIo::new(target));
+        block_on(async {
+            swd.reset().await.unwrap();
+            let ap = ApAddress::Adiv6(0x1000);
+            let idr = swd.read_ap_register::<Idr>(ap).await.unwrap();
+            assert_eq!(u32::from(idr), 0x24770011);
+            let mut memap = swd.memap(ap);
+            assert_eq!(memap.base().await.map(u32::from), Ok(0xe00ff003));
+            memap.write_32(0x2000_0000, 0x1234_5678).await.unwrap();
+            assert_eq!(memap.read_32(0x2000_0000).await, Ok(0x1234_5678));
+        });
+        assert_eq!(swd.io.target.aps[0].memory.read_word(0x2000_0000), Ok(0));
+        assert_eq!(
+            swd.io.target.aps[1].memory.read_word(0x2000_0000),
+            Ok(0x1234_5678)
+        );
+    }
+}
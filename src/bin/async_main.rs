@@ -4,13 +4,20 @@
 use alloc::{vec, vec::Vec};
 use embassy_executor::Spawner;
 use embassy_net::tcp::TcpSocket;
-use embassy_time::Timer;
+use embassy_time::{Duration, Timer};
 use embedded_io_async::{Read, Write};
 use esp_backtrace as _;
 use esp_hal::clock::CpuClock;
+use esp_swd_probe::ap::{ApAddress, ApInfo, ApKind, MAX_APS};
+use esp_swd_probe::cortexm::{CoreError, CoreStatus, ResetStrategy};
+#[cfg(not(feature = "spi-io"))]
+use esp_swd_probe::io::FlexIo;
+#[cfg(feature = "spi-io")]
+use esp_swd_probe::io::SpiIo;
+use esp_swd_probe::io::SwdIo;
 use esp_swd_probe::net::{start_net, wait_for_dhcp, wait_for_link};
-use esp_swd_probe::registers::ap::Idr;
-use esp_swd_probe::registers::dp::{CtrlStat, Idcode};
+use esp_swd_probe::nreset::ResetError;
+use esp_swd_probe::registers::dp::{CtrlStat, Dpidr};
 use esp_swd_probe::swd::{APnDP, RequestError, Swd};
 
 use esp_swd_probe::wifi;
@@ -19,43 +26,38 @@ use thiserror::Error;
 
 extern crate alloc;
 
-pub async fn test_swd(swd: &mut Swd<'_>) -> Result<(), RequestError> {
+pub async fn test_swd(swd: &mut Swd<impl SwdIo>) -> Result<(), RequestError> {
     swd.swd_clock(false).await;
     Timer::after_nanos(1000).await;
 
-    swd.reset().await;
+    info!("wakeup = {:?}", swd.reset().await?);
 
-    info!("idcode = {:x?}", swd.read_dp_register::<Idcode>().await?);
+    info!("dpidr = {}", swd.read_dp_register::<Dpidr>().await?);
 
     swd.write_dp_register(CtrlStat::default()).await?;
+    info!("ctrlstat = {:x?}", swd.power_up().await?);
 
-    swd.modify_dp_register::<CtrlStat>(|reg| {
-        info!("ctrlstat = {:x?}", reg);
-        reg.set_csyspwrupreq(true)
-            .set_cdbgpwrupreq(true)
-            .set_cdbgrstreq(true)
-    })
-    .await?;
+    let aps = swd.enumerate_aps(false).await?;
+    let Some(ap) = aps.iter().find(|ap| matches!(ap.kind(), ApKind::MemAp(_))) else {
+        info!("No MEM-AP found");
+        return Ok(());
+    };
+    info!("IDR = {}", ap.idr);
 
-    swd.modify_dp_register::<CtrlStat>(|reg| {
-        info!("ctrlstat = {:x?}", reg);
-        reg.set_cdbgrstreq(false)
-    })
-    .await?;
-
-    const AP: u8 = 0;
-
-    let idr = swd.read_ap_register::<Idr>(AP).await?;
-    info!("IDR = {:x?}", idr);
-
-    let mut memap = swd.memap(AP);
+    let mut memap = swd.memap(ap.address);
     let base = memap.base().await?;
     info!("BASE = {:x?}", base);
-    for i in (0x0ff0..0x1000).step_by(4) {
-        let address = base.address() + i;
-        let value = memap.read_32(address).await?;
-        info!("{:#010x} = {:#010x}", address, value);
+    for component in memap.walk_base_rom_table().await? {
+        info!(
+            "{:width$}{:#010x} {:?} {}",
+            "",
+            component.base,
+            component.kind,
+            component.id,
+            width = 2 * component.depth as usize
+        );
     }
+    info!("core = {:?}", swd.cortex_m(ap.address).status().await?);
 
     swd.write_dp_register(CtrlStat::default()).await?;
     info!("status = {:x?}", swd.read_dp_register::<CtrlStat>().await);
@@ -70,6 +72,26 @@ pub enum Command {
     ReadAp(u8),
     WriteAp(u8, u32),
     SwjSequence(u8, u64),
+    SetClock(u32),
+    SetWaitLimits(u32, u32),
+    SelectTarget(u32, u8),
+    /// Go back to a single-drop target after `SelectTarget`.
+    ClearTarget,
+    EnumerateAps(bool),
+    SetResetStrategy(ResetStrategy),
+    /// Reset the target through the Cortex-M core behind an AP, optionally
+    /// halting it at the reset vector.
+    Reset(u8, bool),
+    Reconnect,
+    SetNreset(bool),
+    ReadNreset,
+    /// Pulse nRESET low for the given number of milliseconds.
+    PulseNreset(u32),
+    /// Connect, optionally under reset, leaving nRESET asserted.
+    Attach(bool),
+    /// Release nRESET after attaching under reset, optionally halting the
+    /// Cortex-M core behind an AP at the reset vector.
+    ReleaseReset(u8, bool),
 }
 
 #[derive(Debug, Error)]
@@ -80,6 +102,8 @@ pub enum CommandError {
     UnknownCommand,
     #[error("Command too short")]
     TooShort,
+    #[error("Invalid argument")]
+    InvalidArgument,
 }
 
 impl TryFrom<&[u8]> for Command {
@@ -127,6 +151,82 @@ impl TryFrom<&[u8]> for Command {
                     u64::from_be_bytes(data[1..].try_into().unwrap()),
                 ))
             }
+            0x05 => {
+                if data.len() < 4 {
+                    return Err(CommandError::TooShort);
+                }
+                Ok(Command::SetClock(u32::from_be_bytes(
+                    data[..4].try_into().unwrap(),
+                )))
+            }
+            0x06 => {
+                if data.len() < 8 {
+                    return Err(CommandError::TooShort);
+                }
+                Ok(Command::SetWaitLimits(
+                    u32::from_be_bytes(data[..4].try_into().unwrap()),
+                    u32::from_be_bytes(data[4..8].try_into().unwrap()),
+                ))
+            }
+            0x07 => {
+                if data.len() < 5 {
+                    return Err(CommandError::TooShort);
+                }
+                Ok(Command::SelectTarget(
+                    u32::from_be_bytes(data[..4].try_into().unwrap()),
+                    data[4],
+                ))
+            }
+            0x08 => {
+                if data.is_empty() {
+                    return Err(CommandError::TooShort);
+                }
+                Ok(Command::EnumerateAps(data[0] != 0))
+            }
+            0x09 => {
+                if data.is_empty() {
+                    return Err(CommandError::TooShort);
+                }
+                let strategy = data[0]
+                    .try_into()
+                    .map_err(|_| CommandError::InvalidArgument)?;
+                Ok(Command::SetResetStrategy(strategy))
+            }
+            0x0a => {
+                if data.len() < 2 {
+                    return Err(CommandError::TooShort);
+                }
+                Ok(Command::Reset(data[0], data[1] != 0))
+            }
+            0x0b => Ok(Command::Reconnect),
+            0x0c => {
+                if data.is_empty() {
+                    return Err(CommandError::TooShort);
+                }
+                Ok(Command::SetNreset(data[0] != 0))
+            }
+            0x0d => Ok(Command::ReadNreset),
+            0x0e => {
+                if data.len() < 4 {
+                    return Err(CommandError::TooShort);
+                }
+                Ok(Command::PulseNreset(u32::from_be_bytes(
+                    data[..4].try_into().unwrap(),
+                )))
+            }
+            0x0f => {
+                if data.is_empty() {
+                    return Err(CommandError::TooShort);
+                }
+                Ok(Command::Attach(data[0] != 0))
+            }
+            0x10 => {
+                if data.len() < 2 {
+                    return Err(CommandError::TooShort);
+                }
+                Ok(Command::ReleaseReset(data[0], data[1] != 0))
+            }
+            0x11 => Ok(Command::ClearTarget),
             _ => Err(CommandError::UnknownCommand),
         }
     }
@@ -171,6 +271,11 @@ pub async fn send_message(sock: &mut TcpSocket<'_>, msg: &[u8]) -> Result<(), Pr
 enum Reply {
     Read(Result<u32, RequestError>),
     Write(Result<(), RequestError>),
+    Aps(Result<heapless::Vec<ApInfo, MAX_APS>, RequestError>),
+    Core(Result<CoreStatus, CoreError>),
+    /// Like `Read` and `Write`, for the nRESET commands.
+    ResetRead(Result<u32, ResetError>),
+    ResetWrite(Result<(), ResetError>),
 }
 
 impl From<Reply> for Vec<u8> {
@@ -188,6 +293,43 @@ impl From<Reply> for Vec<u8> {
                 msg.push(0x00);
             }
             Reply::Write(Err(err)) => msg.push(err.into()),
+            Reply::Aps(Ok(aps)) => {
+                msg.push(0x00);
+                // Each AP is 0x00 and APSEL, or 0x01 and the 64-bit ADIv6
+                // base address, followed by IDR.
+                for ap in aps {
+                    match ap.address {
+                        ApAddress::Adiv5(apsel) => {
+                            msg.push(0x00);
+                            msg.push(apsel);
+                        }
+                        ApAddress::Adiv6(base) => {
+                            msg.push(0x01);
+                            msg.extend(base.to_be_bytes());
+                        }
+                    }
+                    msg.extend(u32::from(ap.idr).to_be_bytes());
+                }
+            }
+            Reply::Aps(Err(err)) => msg.push(err.into()),
+            Reply::Core(Ok(status)) => {
+                msg.push(0x00);
+                msg.push(
+                    status.halted as u8
+                        | (status.sleeping as u8) << 1
+                        | (status.lockup as u8) << 2
+                        | (status.retired as u8) << 3
+                        | (status.reset as u8) << 4,
+                );
+            }
+            Reply::Core(Err(err)) => msg.push(err.into()),
+            Reply::ResetRead(Ok(value)) => {
+                msg.push(0x00);
+                msg.extend(value.to_be_bytes())
+            }
+            Reply::ResetRead(Err(err)) => msg.push(err.into()),
+            Reply::ResetWrite(Ok(())) => msg.push(0x00),
+            Reply::ResetWrite(Err(err)) => msg.push(err.into()),
         }
         msg
     }
@@ -199,8 +341,9 @@ pub fn a_to_bits(a: u8) -> [bool; 2] {
 
 pub async fn handle_connection(
     sock: &mut TcpSocket<'_>,
-    swd: &mut Swd<'_>,
+    swd: &mut Swd<impl SwdIo>,
 ) -> Result<(), ProtocolError> {
+    let mut reset_strategy = ResetStrategy::default();
     loop {
         let msg = recv_message(sock).await?;
         let cmd: Command = (&msg[..]).try_into()?;
@@ -216,6 +359,39 @@ pub async fn handle_connection(
                 swd.swj_sequence(bit_len, bits).await;
                 Reply::Write(Ok(()))
             }
+            Command::SetClock(hz) => Reply::Read(Ok(swd.set_frequency(hz).await)),
+            Command::SetWaitLimits(retries, timeout_ms) => {
+                swd.set_wait_limits(retries as usize, Duration::from_millis(timeout_ms as u64));
+                Reply::Write(Ok(()))
+            }
+            Command::SelectTarget(targetid, instance) => {
+                Reply::Read(swd.select_target(targetid, instance).await.map(Into::into))
+            }
+            Command::ClearTarget => {
+                swd.clear_target();
+                Reply::Write(Ok(()))
+            }
+            Command::EnumerateAps(past_gaps) => Reply::Aps(swd.enumerate_aps(past_gaps).await),
+            Command::SetResetStrategy(strategy) => {
+                reset_strategy = strategy;
+                Reply::Write(Ok(()))
+            }
+            Command::Reset(ap, halt) => {
+                Reply::Core(swd.cortex_m(ap).reset(reset_strategy, halt).await)
+            }
+            Command::Reconnect => Reply::Read(swd.reconnect().await.map(Into::into)),
+            Command::SetNreset(true) => Reply::ResetWrite(swd.assert_reset()),
+            Command::SetNreset(false) => Reply::ResetWrite(swd.deassert_reset().await),
+            Command::ReadNreset => Reply::ResetRead(swd.reset_asserted().map(Into::into)),
+            Command::PulseNreset(ms) => {
+                Reply::ResetWrite(swd.pulse_reset(Duration::from_millis(ms as u64)).await)
+            }
+            Command::Attach(under_reset) => {
+                Reply::ResetRead(swd.attach(under_reset).await.map(Into::into))
+            }
+            Command::ReleaseReset(ap, halt) => {
+                Reply::Core(swd.cortex_m(ap).release_reset(halt).await)
+            }
         };
         debug!("Reply: {:x?}", reply);
         let msg: Vec<u8> = reply.into();
@@ -254,7 +430,15 @@ async fn main(spawner: Spawner) {
     let mut txbuf = [0u8; 4096];
     let mut rxbuf = [0u8; 4096];
 
-    let mut swd = Swd::new(peripherals.GPIO21, peripherals.GPIO20);
+    #[cfg(not(feature = "spi-io"))]
+    let io = FlexIo::new(peripherals.GPIO21, peripherals.GPIO20);
+    #[cfg(feature = "spi-io")]
+    let io = SpiIo::new(
+        peripherals.SPI2,
+        peripherals.GPIO21.into(),
+        peripherals.GPIO20.into(),
+    );
+    let mut swd = Swd::new(io.with_nreset(peripherals.GPIO10));
 
     loop {
         let mut socket = TcpSocket::new(stack, &mut rxbuf, &mut txbuf);
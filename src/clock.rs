@@ -0,0 +1,89 @@
+use core::hint::black_box;
+
+use embassy_time::{Instant, Timer};
+
+/// Half periods at or above this use the embassy timer instead of spinning.
+const TIMER_THRESHOLD_NS: u32 = 50_000;
+
+pub const DEFAULT_FREQUENCY: u32 = 1_000_000;
+/// Range of SWCLK frequencies `Swd::set_frequency` accepts, anything outside
+/// is clamped.
+pub const MIN_FREQUENCY: u32 = 1_000;
+pub const MAX_FREQUENCY: u32 = 10_000_000;
+
+/// Delay between SWCLK edges.
+///
+/// Fast clocks busy-wait for a calibrated number of loop iterations, slow
+/// clocks yield to the executor through the embassy timer queue.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SwdClock {
+    half_period_ns: u32,
+    spins: u32,
+    spins_per_ms: u32,
+}
+
+impl SwdClock {
+    pub fn new() -> Self {
+        let mut clock = Self {
+            half_period_ns: 0,
+            spins: 0,
+            spins_per_ms: calibrate(),
+        };
+        clock.set_half_period(500_000_000 / DEFAULT_FREQUENCY);
+        clock
+    }
+
+    pub fn half_period_ns(&self) -> u32 {
+        self.half_period_ns
+    }
+
+    pub fn is_timer(&self) -> bool {
+        self.half_period_ns >= TIMER_THRESHOLD_NS
+    }
+
+    /// Set the delay between edges, not counting the time spent driving the pins.
+    pub fn set_half_period(&mut self, half_period_ns: u32) {
+        self.half_period_ns = half_period_ns;
+        self.spins = self.ns_to_spins(half_period_ns);
+    }
+
+    /// Shorten the busy-wait by the time the pin accesses take per half period.
+    pub fn compensate(&mut self, overhead_ns: u32) {
+        if !self.is_timer() {
+            self.spins = self.ns_to_spins(self.half_period_ns.saturating_sub(overhead_ns));
+        }
+    }
+
+    fn ns_to_spins(&self, ns: u32) -> u32 {
+        ((ns as u64 * self.spins_per_ms as u64) / 1_000_000) as u32
+    }
+
+    pub async fn wait(&self) {
+        if self.is_timer() {
+            Timer::after_nanos(self.half_period_ns as u64).await
+        } else {
+            spin(self.spins)
+        }
+    }
+}
+
+impl Default for SwdClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn spin(spins: u32) {
+    for i in 0..spins {
+        black_box(i);
+    }
+}
+
+/// Count busy-wait iterations per millisecond.
+fn calibrate() -> u32 {
+    const SPINS: u32 = 100_000;
+    let start = Instant::now();
+    spin(SPINS);
+    let elapsed = start.elapsed().as_micros().max(1);
+    ((SPINS as u64 * 1000) / elapsed).clamp(1, u32::MAX as u64) as u32
+}
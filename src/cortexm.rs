@@ -0,0 +1,781 @@
+//! Run control and core register access of Cortex-M cores through the debug
+//! registers in the SCS.
+
+use embassy_time::{Duration, Instant};
+use log::info;
+use thiserror::Error;
+
+use crate::ap::ApAddress;
+use crate::io::SwdIo;
+use crate::memap::MemAp;
+use crate::nreset::{ResetError, DEFAULT_RESET_PULSE};
+use crate::registers::ap::memap::{AddrInc, Size, Tar, BD0, BD1, BD2};
+use crate::registers::cortexm::{
+    aircr::VECTKEY, dhcsr::DBGKEY, Aircr, Dcrsr, Demcr, Dhcsr, MemoryRegister, ReadRegister,
+    WriteRegister,
+};
+use crate::swd::{RequestError, Swd};
+
+/// How long `halt` and `step` wait for the core to report it halted.
+pub const DEFAULT_HALT_TIMEOUT: Duration = Duration::from_millis(100);
+/// How long a core register transfer may take to set DHCSR.S_REGRDY.
+pub const REGISTER_TIMEOUT: Duration = Duration::from_millis(10);
+/// How long `reset` waits for the core to report it was reset.
+pub const RESET_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// How `CortexM::reset` resets the target.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ResetStrategy {
+    /// System reset requested through AIRCR.SYSRESETREQ.
+    #[default]
+    SysResetReq,
+    /// Core only reset through AIRCR.VECTRESET, ARMv7-M only.
+    VectReset,
+    /// Debug reset through CTRL/STAT.CDBGRSTREQ, where the DP implements it.
+    DebugPort,
+    /// Pulse of the nRESET line, see `Swd::pulse_reset`.
+    Hardware,
+}
+
+impl TryFrom<u8> for ResetStrategy {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(ResetStrategy::SysResetReq),
+            1 => Ok(ResetStrategy::VectReset),
+            2 => Ok(ResetStrategy::DebugPort),
+            3 => Ok(ResetStrategy::Hardware),
+            x => Err(x),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Error, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CoreError {
+    #[error("{0}")]
+    Request(#[from] RequestError),
+    #[error("Core did not halt in time")]
+    HaltTimeout,
+    #[error("Core is not halted")]
+    NotHalted,
+    #[error("Core register transfer did not complete")]
+    RegisterTimeout,
+    #[error("Core did not come out of reset")]
+    ResetTimeout,
+    #[error("No core register {0:?}")]
+    InvalidRegister(CoreRegister),
+    #[error("No nRESET pin")]
+    NoResetPin,
+}
+
+impl From<ResetError> for CoreError {
+    fn from(value: ResetError) -> Self {
+        match value {
+            ResetError::Request(err) => CoreError::Request(err),
+            ResetError::NoResetPin => CoreError::NoResetPin,
+        }
+    }
+}
+
+impl From<CoreError> for u8 {
+    fn from(value: CoreError) -> Self {
+        match value {
+            CoreError::Request(err) => err.into(),
+            CoreError::HaltTimeout => 0x10,
+            CoreError::NotHalted => 0x11,
+            CoreError::RegisterTimeout => 0x12,
+            CoreError::ResetTimeout => 0x13,
+            CoreError::InvalidRegister(_) => 0x14,
+            CoreError::NoResetPin => ResetError::NoResetPin.into(),
+        }
+    }
+}
+
+/// A core register as selected by DCRSR.REGSEL.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CoreRegister {
+    /// R0 to R15, where R13 to R15 are also `Sp`, `Lr` and `Pc`.
+    R(u8),
+    Sp,
+    Lr,
+    /// Debug return address, where execution continues when resumed.
+    Pc,
+    Xpsr,
+    Msp,
+    Psp,
+    Control,
+    Faultmask,
+    Basepri,
+    Primask,
+    /// Only present with the floating point extension, as are `S`.
+    Fpscr,
+    /// S0 to S31.
+    S(u8),
+}
+
+impl CoreRegister {
+    /// R0 to R15 and xPSR, in the order of a GDB `g` packet.
+    pub const GENERAL: [CoreRegister; 17] = [
+        CoreRegister::R(0),
+        CoreRegister::R(1),
+        CoreRegister::R(2),
+        CoreRegister::R(3),
+        CoreRegister::R(4),
+        CoreRegister::R(5),
+        CoreRegister::R(6),
+        CoreRegister::R(7),
+        CoreRegister::R(8),
+        CoreRegister::R(9),
+        CoreRegister::R(10),
+        CoreRegister::R(11),
+        CoreRegister::R(12),
+        CoreRegister::Sp,
+        CoreRegister::Lr,
+        CoreRegister::Pc,
+        CoreRegister::Xpsr,
+    ];
+
+    /// DCRSR.REGSEL of the register, `None` for `R` and `S` indices past
+    /// R15 and S31.
+    pub fn regsel(&self) -> Option<u8> {
+        let regsel = match *self {
+            CoreRegister::R(n) if n < 16 => n,
+            CoreRegister::R(_) => return None,
+            CoreRegister::Sp => 13,
+            CoreRegister::Lr => 14,
+            CoreRegister::Pc => 15,
+            CoreRegister::Xpsr => 16,
+            CoreRegister::Msp => 17,
+            CoreRegister::Psp => 18,
+            CoreRegister::Control
+            | CoreRegister::Faultmask
+            | CoreRegister::Basepri
+            | CoreRegister::Primask => 20,
+            CoreRegister::Fpscr => 33,
+            CoreRegister::S(n) if n < 32 => 0x40 | n,
+            CoreRegister::S(_) => return None,
+        };
+        Some(regsel)
+    }
+
+    /// CONTROL, FAULTMASK, BASEPRI and PRIMASK share one REGSEL, a byte each.
+    fn shift(&self) -> Option<u32> {
+        match self {
+            CoreRegister::Control => Some(24),
+            CoreRegister::Faultmask => Some(16),
+            CoreRegister::Basepri => Some(8),
+            CoreRegister::Primask => Some(0),
+            _ => None,
+        }
+    }
+}
+
+/// Core state decoded from DHCSR.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CoreStatus {
+    pub halted: bool,
+    /// Sleeping in WFI or WFE, only meaningful while not halted.
+    pub sleeping: bool,
+    pub lockup: bool,
+    /// An instruction completed since the last status read.
+    pub retired: bool,
+    /// The core was reset since the last status read.
+    pub reset: bool,
+}
+
+impl From<Dhcsr> for CoreStatus {
+    fn from(dhcsr: Dhcsr) -> Self {
+        Self {
+            halted: dhcsr.s_halt(),
+            sleeping: dhcsr.s_sleep(),
+            lockup: dhcsr.s_lockup(),
+            retired: dhcsr.s_retire_st(),
+            reset: dhcsr.s_reset_st(),
+        }
+    }
+}
+
+/// A Cortex-M core behind a MEM-AP.
+pub struct CortexM<'swd, Io> {
+    memap: MemAp<'swd, Io>,
+}
+
+impl<Io: SwdIo> Swd<Io> {
+    pub fn cortex_m(&mut self, ap: impl Into<ApAddress>) -> CortexM<'_, Io> {
+        CortexM::new(self.memap(ap))
+    }
+}
+
+impl<'swd, Io: SwdIo> CortexM<'swd, Io> {
+    pub fn new(memap: MemAp<'swd, Io>) -> Self {
+        Self { memap }
+    }
+
+    /// The MEM-AP the core is behind, for plain memory access.
+    pub fn memap(&mut self) -> &mut MemAp<'swd, Io> {
+        &mut self.memap
+    }
+
+    pub async fn read_register<Reg: ReadRegister>(&mut self) -> Result<Reg, RequestError> {
+        Ok(self.memap.read_32(Reg::ADDRESS).await?.into())
+    }
+
+    pub async fn write_register<Reg: WriteRegister>(
+        &mut self,
+        reg: Reg,
+    ) -> Result<(), RequestError> {
+        self.memap.write_32(Reg::ADDRESS, reg.into()).await
+    }
+
+    /// Write the control bits of DHCSR, the key is filled in.
+    async fn write_dhcsr(&mut self, dhcsr: Dhcsr) -> Result<(), RequestError> {
+        self.write_register(dhcsr.set_dbgkey(DBGKEY)).await
+    }
+
+    /// Read the core state. Reading clears the retired and reset flags.
+    pub async fn status(&mut self) -> Result<CoreStatus, RequestError> {
+        Ok(self.read_register::<Dhcsr>().await?.into())
+    }
+
+    /// Enable halting debug and request a halt.
+    pub async fn halt(&mut self) -> Result<CoreStatus, CoreError> {
+        self.write_dhcsr(Dhcsr::default().set_c_debugen(true).set_c_halt(true))
+            .await?;
+        self.wait_for_halt(DEFAULT_HALT_TIMEOUT).await
+    }
+
+    /// Resume execution, leaving halting debug enabled so breakpoints and
+    /// watchpoints still halt the core.
+    pub async fn run(&mut self) -> Result<(), RequestError> {
+        self.write_dhcsr(Dhcsr::default().set_c_debugen(true)).await
+    }
+
+    /// Execute a single instruction with interrupts masked and halt again.
+    ///
+    /// C_MASKINTS is restored afterwards, so interrupts stay masked for `run`
+    /// only if they were before the step.
+    pub async fn step(&mut self) -> Result<CoreStatus, CoreError> {
+        let dhcsr = self.read_register::<Dhcsr>().await?;
+        if !dhcsr.s_halt() {
+            return Err(CoreError::NotHalted);
+        }
+        // C_MASKINTS may only change while halted, so set it before stepping.
+        let halted = Dhcsr::default().set_c_debugen(true).set_c_halt(true);
+        let masked = halted.set_c_maskints(true);
+        self.write_dhcsr(masked).await?;
+        self.write_dhcsr(masked.set_c_halt(false).set_c_step(true))
+            .await?;
+        let status = self.wait_for_halt(DEFAULT_HALT_TIMEOUT).await?;
+        self.write_dhcsr(halted.set_c_maskints(dhcsr.c_maskints()))
+            .await?;
+        Ok(status)
+    }
+
+    /// Reset the target with `strategy` and, with `halt`, stop the core at the
+    /// reset vector.
+    ///
+    /// Reconnects when the reset took the SWD link down.
+    pub async fn reset(
+        &mut self,
+        strategy: ResetStrategy,
+        halt: bool,
+    ) -> Result<CoreStatus, CoreError> {
+        let demcr = self.catch_reset(halt).await?;
+        let aircr = Aircr::default().set_vectkey(VECTKEY);
+        match strategy {
+            // The target may reset before acknowledging the write.
+            ResetStrategy::SysResetReq => {
+                let _ = self.write_register(aircr.set_sysresetreq(true)).await;
+            }
+            ResetStrategy::VectReset => {
+                let _ = self.write_register(aircr.set_vectreset(true)).await;
+            }
+            ResetStrategy::DebugPort => self.memap.swd().debug_reset().await?,
+            ResetStrategy::Hardware => self.memap.swd().pulse_reset(DEFAULT_RESET_PULSE).await?,
+        }
+        self.finish_reset(halt, demcr).await
+    }
+
+    /// Release nRESET after `Swd::attach` connected under reset and, with
+    /// `halt`, stop the core at the reset vector.
+    pub async fn release_reset(&mut self, halt: bool) -> Result<CoreStatus, CoreError> {
+        let demcr = self.catch_reset(halt).await?;
+        self.memap.swd().deassert_reset().await?;
+        self.finish_reset(halt, demcr).await
+    }
+
+    /// Clear S_RESET_ST, so it only shows the coming reset, and with `halt`
+    /// enable vector catch. Returns DEMCR to restore afterwards.
+    async fn catch_reset(&mut self, halt: bool) -> Result<Demcr, CoreError> {
+        let halted = self.status().await?.halted;
+        let demcr = self.read_register::<Demcr>().await?;
+        if halt {
+            // Vector catch needs halting debug enabled.
+            self.write_dhcsr(Dhcsr::default().set_c_debugen(true).set_c_halt(halted))
+                .await?;
+            self.write_register(demcr.set_vc_corereset(true)).await?;
+        }
+        Ok(demcr)
+    }
+
+    /// Wait for the reset to show up and with `halt` for the core to stop.
+    async fn finish_reset(&mut self, halt: bool, demcr: Demcr) -> Result<CoreStatus, CoreError> {
+        self.wait_for_reset().await?;
+        let status = match halt {
+            true => {
+                let status = self.wait_for_halt(DEFAULT_HALT_TIMEOUT).await?;
+                self.write_register(demcr).await?;
+                status
+            }
+            false => self.status().await?,
+        };
+        Ok(CoreStatus {
+            reset: true,
+            ..status
+        })
+    }
+
+    /// Poll DHCSR until S_RESET_ST shows the core was reset, reconnecting
+    /// while the target does not respond.
+    async fn wait_for_reset(&mut self) -> Result<(), CoreError> {
+        let start = Instant::now();
+        loop {
+            match self.status().await {
+                Ok(status) if status.reset => return Ok(()),
+                Ok(_) => {}
+                Err(err) => {
+                    info!("Reconnecting after reset: {:?}", err);
+                    self.memap.invalidate();
+                    if let Err(err) = self.memap.swd().reconnect().await {
+                        info!("Reconnect failed: {:?}", err);
+                    }
+                }
+            }
+            if start.elapsed() >= RESET_TIMEOUT {
+                return Err(CoreError::ResetTimeout);
+            }
+        }
+    }
+
+    /// Read a core register, the core has to be halted.
+    pub async fn read_core_register(&mut self, reg: CoreRegister) -> Result<u32, CoreError> {
+        let mut value = [0];
+        self.read_core_registers(&[reg], &mut value).await?;
+        Ok(value[0])
+    }
+
+    /// Read several core registers, the core has to be halted.
+    ///
+    /// TAR is written once and DHCSR, DCRSR and DCRDR are reached through
+    /// the banked data registers, so each register costs a DCRSR write and
+    /// two reads.
+    pub async fn read_core_registers(
+        &mut self,
+        regs: &[CoreRegister],
+        values: &mut [u32],
+    ) -> Result<(), CoreError> {
+        self.select_debug_block().await?;
+        for (reg, value) in regs.iter().zip(values.iter_mut()) {
+            let regsel = reg.regsel().ok_or(CoreError::InvalidRegister(*reg))?;
+            let data = self.transfer_core_register(regsel, None).await?;
+            *value = match reg.shift() {
+                Some(shift) => (data >> shift) & 0xff,
+                None => data,
+            };
+        }
+        Ok(())
+    }
+
+    /// Read R0 to R15 and xPSR, see `CoreRegister::GENERAL`.
+    pub async fn read_general_registers(&mut self) -> Result<[u32; 17], CoreError> {
+        let mut values = [0; 17];
+        self.read_core_registers(&CoreRegister::GENERAL, &mut values)
+            .await?;
+        Ok(values)
+    }
+
+    /// Write a core register, the core has to be halted.
+    pub async fn write_core_register(
+        &mut self,
+        reg: CoreRegister,
+        value: u32,
+    ) -> Result<(), CoreError> {
+        let regsel = reg.regsel().ok_or(CoreError::InvalidRegister(reg))?;
+        self.select_debug_block().await?;
+        let value = match reg.shift() {
+            Some(shift) => {
+                let old = self.transfer_core_register(regsel, None).await?;
+                (old & !(0xff << shift)) | ((value & 0xff) << shift)
+            }
+            None => value,
+        };
+        self.transfer_core_register(regsel, Some(value)).await?;
+        Ok(())
+    }
+
+    /// Point TAR at DHCSR, making DHCSR, DCRSR, DCRDR and DEMCR available
+    /// as BD0 to BD3.
+    async fn select_debug_block(&mut self) -> Result<(), RequestError> {
+        self.memap.set_transfer(Size::Word, AddrInc::Off).await?;
+        self.memap
+            .write_register(Tar::default().set_address(Dhcsr::ADDRESS))
+            .await
+    }
+
+    /// Move a register through DCRDR, with the debug block selected.
+    async fn transfer_core_register(
+        &mut self,
+        regsel: u8,
+        write: Option<u32>,
+    ) -> Result<u32, CoreError> {
+        if let Some(value) = write {
+            self.memap
+                .write_register(BD2::default().set_data(value))
+                .await?;
+        }
+        let dcrsr = Dcrsr::default()
+            .set_regsel(regsel)
+            .set_regwnr(write.is_some());
+        self.memap
+            .write_register(BD1::default().set_data(dcrsr.into()))
+            .await?;
+        let start = Instant::now();
+        loop {
+            let dhcsr = Dhcsr::from(self.memap.read_register::<BD0>().await?.data());
+            if !dhcsr.s_halt() {
+                return Err(CoreError::NotHalted);
+            }
+            if dhcsr.s_regrdy() {
+                break;
+            }
+            if start.elapsed() >= REGISTER_TIMEOUT {
+                return Err(CoreError::RegisterTimeout);
+            }
+        }
+        match write {
+            Some(value) => Ok(value),
+            None => Ok(self.memap.read_register::<BD2>().await?.data()),
+        }
+    }
+
+    /// Poll DHCSR until the core halts or `timeout` passes.
+    ///
+    /// The returned status accumulates the sticky flags of every read.
+    pub async fn wait_for_halt(&mut self, timeout: Duration) -> Result<CoreStatus, CoreError> {
+        let start = Instant::now();
+        let mut sticky = CoreStatus::default();
+        loop {
+            let status = self.status().await?;
+            sticky.retired |= status.retired;
+            sticky.reset |= status.reset;
+            if status.halted {
+                return Ok(CoreStatus {
+                    retired: sticky.retired,
+                    reset: sticky.reset,
+                    ..status
+                });
+            }
+            if start.elapsed() >= timeout {
+                info!("Core did not halt: {:?}", status);
+                return Err(CoreError::HaltTimeout);
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "sim"))]
+mod tests {
+    use super::*;
+    use crate::registers::dp::CtrlStat;
+    use crate::sim::{block_on, CoreSim, MemApSim, Memory, SimIo, Target, IDCODE};
+
+    fn core_swd() -> Swd<SimIo<Target>> {
+        let memory = Memory::new().with_core(CoreSim::new());
+        let target = Target::new(IDCODE).with_ap(MemApSim::new(0xe00ff003, memory));
+        Swd::new(SimIo::new(target))
+    }
+
+    fn core_sim(swd: &mut Swd<SimIo<Target>>) -> &mut CoreSim {
+        swd.io.target.aps[0].memory.core_mut().unwrap()
+    }
+
+    #[test]
+    fn halt() {
+        let mut swd = core_swd();
+        block_on(async {
+            swd.reset().await.unwrap();
+            let mut core = swd.cortex_m(0);
+            let status = core.status().await.unwrap();
+            assert!(!status.halted && status.reset);
+            let status = core.halt().await.unwrap();
+            assert!(status.halted && !status.reset);
+            assert!(core.status().await.unwrap().halted);
+        });
+        let core = core_sim(&mut swd);
+        assert!(core.halted && core.debugen);
+    }
+
+    #[test]
+    fn step() {
+        let mut swd = core_swd();
+        block_on(async {
+            swd.reset().await.unwrap();
+            let mut core = swd.cortex_m(0);
+            assert_eq!(core.step().await, Err(CoreError::NotHalted));
+            core.halt().await.unwrap();
+            core.write_core_register(CoreRegister::Pc, 0x0800_0100)
+                .await
+                .unwrap();
+            let status = core.step().await.unwrap();
+            assert!(status.halted && status.retired);
+            assert_eq!(
+                core.read_core_register(CoreRegister::Pc).await,
+                Ok(0x0800_0102)
+            );
+        });
+        assert_eq!(core_sim(&mut swd).steps, 1);
+        assert!(!core_sim(&mut swd).maskints);
+
+        // Interrupts masked by the debugger stay masked.
+        block_on(async {
+            let mut core = swd.cortex_m(0);
+            let masked = Dhcsr::default()
+                .set_c_debugen(true)
+                .set_c_halt(true)
+                .set_c_maskints(true);
+            core.write_dhcsr(masked).await.unwrap();
+            core.step().await.unwrap();
+            assert_eq!(
+                core.read_core_register(CoreRegister::Pc).await,
+                Ok(0x0800_0104)
+            );
+        });
+        assert!(core_sim(&mut swd).maskints);
+    }
+
+    #[test]
+    fn core_registers() {
+        let mut swd = core_swd();
+        block_on(async {
+            swd.reset().await.unwrap();
+            let mut core = swd.cortex_m(0);
+            core.halt().await.unwrap();
+            for n in 0..16 {
+                core.write_core_register(CoreRegister::R(n), 0x100 + u32::from(n))
+                    .await
+                    .unwrap();
+            }
+            core.write_core_register(CoreRegister::Xpsr, 0x0100_0000)
+                .await
+                .unwrap();
+            let values = core.read_general_registers().await.unwrap();
+            assert_eq!(
+                values[..16],
+                core::array::from_fn::<u32, 16, _>(|n| 0x100 + n as u32)
+            );
+            assert_eq!(values[16], 0x0100_0000);
+            assert_eq!(core.read_core_register(CoreRegister::Sp).await, Ok(0x10d));
+            assert_eq!(core.read_core_register(CoreRegister::Lr).await, Ok(0x10e));
+            assert_eq!(core.read_core_register(CoreRegister::Pc).await, Ok(0x10f));
+
+            core.write_core_register(CoreRegister::Msp, 0x2000_1000)
+                .await
+                .unwrap();
+            core.write_core_register(CoreRegister::Psp, 0x2000_0800)
+                .await
+                .unwrap();
+            assert_eq!(
+                core.read_core_register(CoreRegister::Msp).await,
+                Ok(0x2000_1000)
+            );
+            assert_eq!(
+                core.read_core_register(CoreRegister::Psp).await,
+                Ok(0x2000_0800)
+            );
+
+            core.write_core_register(CoreRegister::Control, 0x2)
+                .await
+                .unwrap();
+            core.write_core_register(CoreRegister::Faultmask, 0x1)
+                .await
+                .unwrap();
+            core.write_core_register(CoreRegister::Basepri, 0x40)
+                .await
+                .unwrap();
+            core.write_core_register(CoreRegister::Primask, 0x1)
+                .await
+                .unwrap();
+            core.write_core_register(CoreRegister::Faultmask, 0x0)
+                .await
+                .unwrap();
+            assert_eq!(
+                core.read_core_register(CoreRegister::Control).await,
+                Ok(0x2)
+            );
+            assert_eq!(
+                core.read_core_register(CoreRegister::Faultmask).await,
+                Ok(0x0)
+            );
+            assert_eq!(
+                core.read_core_register(CoreRegister::Basepri).await,
+                Ok(0x40)
+            );
+            assert_eq!(
+                core.read_core_register(CoreRegister::Primask).await,
+                Ok(0x1)
+            );
+        });
+        let registers = &core_sim(&mut swd).registers;
+        assert_eq!(registers[15], 0x10f);
+        assert_eq!(registers[16], 0x0100_0000);
+        assert_eq!(registers[17..19], [0x2000_1000, 0x2000_0800]);
+        assert_eq!(registers[20], 0x0200_4001);
+    }
+
+    #[test]
+    fn invalid_registers() {
+        assert_eq!(CoreRegister::R(15).regsel(), Some(15));
+        assert_eq!(CoreRegister::R(16).regsel(), None);
+        assert_eq!(CoreRegister::S(31).regsel(), Some(0x5f));
+        assert_eq!(CoreRegister::S(32).regsel(), None);
+
+        let mut swd = core_swd();
+        block_on(async {
+            swd.reset().await.unwrap();
+            let mut core = swd.cortex_m(0);
+            core.halt().await.unwrap();
+            core.write_core_register(CoreRegister::R(0), 0x1234)
+                .await
+                .unwrap();
+            assert_eq!(
+                core.read_core_register(CoreRegister::R(16)).await,
+                Err(CoreError::InvalidRegister(CoreRegister::R(16)))
+            );
+            assert_eq!(
+                core.write_core_register(CoreRegister::S(32), 0).await,
+                Err(CoreError::InvalidRegister(CoreRegister::S(32)))
+            );
+        });
+        assert_eq!(core_sim(&mut swd).registers[0], 0x1234);
+    }
+
+    #[test]
+    fn run() {
+        let mut swd = core_swd();
+        block_on(async {
+            swd.reset().await.unwrap();
+            let mut core = swd.cortex_m(0);
+            core.halt().await.unwrap();
+            core.run().await.unwrap();
+            let status = core.status().await.unwrap();
+            assert!(!status.halted);
+            assert_eq!(
+                core.read_core_register(CoreRegister::R(0)).await,
+                Err(CoreError::NotHalted)
+            );
+        });
+        let core = core_sim(&mut swd);
+        assert!(!core.halted && core.debugen);
+    }
+
+    #[test]
+    fn sysresetreq() {
+        let mut swd = core_swd();
+        block_on(async {
+            swd.reset().await.unwrap();
+            let mut core = swd.cortex_m(0);
+            core.status().await.unwrap();
+            let status = core.reset(ResetStrategy::SysResetReq, false).await;
+            assert!(status.is_ok_and(|status| status.reset && !status.halted));
+        });
+        assert_eq!(core_sim(&mut swd).resets, 1);
+    }
+
+    #[test]
+    fn vectreset() {
+        let mut swd = core_swd();
+        // A core only reset leaves the debug port alone.
+        swd.io.target.reset_drops_link = true;
+        block_on(async {
+            swd.reset().await.unwrap();
+            let mut core = swd.cortex_m(0);
+            let status = core.reset(ResetStrategy::VectReset, true).await;
+            assert!(status.is_ok_and(|status| status.reset && status.halted));
+        });
+        let core = core_sim(&mut swd);
+        assert_eq!(core.resets, 1);
+        assert!(core.halted && !core.take_system_reset());
+    }
+
+    #[test]
+    fn debug_port_reset() {
+        let mut swd = core_swd();
+        block_on(async {
+            swd.reset().await.unwrap();
+            let mut core = swd.cortex_m(0);
+            core.status().await.unwrap();
+            let status = core.reset(ResetStrategy::DebugPort, false).await;
+            assert!(status.is_ok_and(|status| status.reset && !status.halted));
+            let ctrlstat = swd.read_dp_register::<CtrlStat>().await.unwrap();
+            assert!(!ctrlstat.cdbgrstreq() && !ctrlstat.cdbgrstack());
+        });
+        assert_eq!(core_sim(&mut swd).resets, 0);
+    }
+
+    #[test]
+    fn hardware_reset() {
+        let mut swd = core_swd();
+        block_on(async {
+            swd.reset().await.unwrap();
+            let mut core = swd.cortex_m(0);
+            core.status().await.unwrap();
+            let status = core.reset(ResetStrategy::Hardware, true).await;
+            assert!(status.is_ok_and(|status| status.reset && status.halted));
+            assert_eq!(swd.reset_asserted(), Ok(false));
+        });
+        let core = core_sim(&mut swd);
+        assert_eq!(core.resets, 0);
+        assert!(core.halted && !core.demcr.vc_corereset());
+    }
+
+    #[test]
+    fn reset_and_halt_reconnects() {
+        let mut swd = core_swd();
+        swd.io.target.reset_drops_link = true;
+        block_on(async {
+            swd.reset().await.unwrap();
+            swd.power_up().await.unwrap();
+            let mut core = swd.cortex_m(0);
+            let status = core.reset(ResetStrategy::SysResetReq, true).await;
+            assert!(status.is_ok_and(|status| status.reset && status.halted));
+            assert!(core.status().await.unwrap().halted);
+        });
+        let core = core_sim(&mut swd);
+        assert_eq!(core.resets, 1);
+        assert!(core.halted && core.debugen);
+        // Vector catch is restored once the core halted.
+        assert!(!core.demcr.vc_corereset());
+    }
+
+    #[test]
+    fn connect_under_reset() {
+        let mut swd = core_swd();
+        core_sim(&mut swd).reset_vector = 0x0800_0100;
+        block_on(async {
+            swd.attach(true).await.unwrap();
+            assert_eq!(swd.reset_asserted(), Ok(true));
+            let mut core = swd.cortex_m(0);
+            assert!(core.status().await.unwrap().reset);
+            let status = core.release_reset(true).await;
+            assert!(status.is_ok_and(|status| status.reset && status.halted));
+            assert_eq!(
+                core.read_core_register(CoreRegister::Pc).await,
+                Ok(0x0800_0100)
+            );
+        });
+        assert_eq!(swd.io.nreset(), Ok(false));
+        let core = core_sim(&mut swd);
+        assert!(core.halted && !core.demcr.vc_corereset());
+    }
+}
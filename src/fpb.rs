@@ -0,0 +1,352 @@
+//! Hardware breakpoints through the Flash Patch and Breakpoint unit.
+//!
+//! Which comparators are in use is read back from FP_COMP on every call, so
+//! the breakpoints survive the `Fpb` that set them.
+
+use heapless::Vec;
+use log::info;
+use thiserror::Error;
+
+use crate::ap::ApAddress;
+use crate::io::SwdIo;
+use crate::memap::MemAp;
+use crate::registers::cortexm::fpb::FP_COMP0;
+use crate::registers::cortexm::{FpComp, FpCtrl, MemoryRegister};
+use crate::swd::{RequestError, Swd};
+
+/// Most comparators FP_CTRL.NUM_CODE can report.
+pub const MAX_COMPARATORS: usize = 127;
+/// Most breakpoints, version 1 comparators hold two.
+pub const MAX_BREAKPOINTS: usize = 2 * MAX_COMPARATORS;
+
+/// Version 1 can only break on the code region below this address.
+const V1_CODE_LIMIT: u32 = 0x2000_0000;
+
+/// FP_COMP.REPLACE of version 1, the halfword of the word to break on.
+const REPLACE_LOWER: u8 = 0b01;
+const REPLACE_UPPER: u8 = 0b10;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FpbRevision {
+    /// ARMv6-M and ARMv7-M, word comparators with halfword REPLACE.
+    V1,
+    /// ARMv8-M, halfword address comparators over the whole address space.
+    V2,
+}
+
+#[derive(Debug, Copy, Clone, Error, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BreakpointError {
+    #[error("{0}")]
+    Request(#[from] RequestError),
+    #[error("All {0} FPB comparators are in use")]
+    NoFreeComparator(u8),
+    #[error("FPB cannot break at {0:#010x}")]
+    UnsupportedAddress(u32),
+    #[error("No breakpoint at {0:#010x}")]
+    NotSet(u32),
+}
+
+impl From<BreakpointError> for u8 {
+    fn from(value: BreakpointError) -> Self {
+        match value {
+            BreakpointError::Request(err) => err.into(),
+            BreakpointError::NoFreeComparator(_) => 0x20,
+            BreakpointError::UnsupportedAddress(_) => 0x21,
+            BreakpointError::NotSet(_) => 0x22,
+        }
+    }
+}
+
+/// The FPB of a Cortex-M core behind a MEM-AP.
+pub struct Fpb<'swd, Io> {
+    memap: MemAp<'swd, Io>,
+    revision: FpbRevision,
+    comparators: u8,
+}
+
+impl<Io: SwdIo> Swd<Io> {
+    pub async fn fpb(&mut self, ap: impl Into<ApAddress>) -> Result<Fpb<'_, Io>, RequestError> {
+        Fpb::new(self.memap(ap)).await
+    }
+}
+
+impl<'swd, Io: SwdIo> Fpb<'swd, Io> {
+    /// Read FP_CTRL to find the revision and the number of comparators.
+    pub async fn new(mut memap: MemAp<'swd, Io>) -> Result<Self, RequestError> {
+        let ctrl = FpCtrl::from(memap.read_32(FpCtrl::ADDRESS).await?);
+        let revision = match ctrl.rev() {
+            0 => FpbRevision::V1,
+            _ => FpbRevision::V2,
+        };
+        info!("FPB {:?} with {} comparators", revision, ctrl.num_code());
+        Ok(Self {
+            memap,
+            revision,
+            comparators: ctrl.num_code(),
+        })
+    }
+
+    pub fn revision(&self) -> FpbRevision {
+        self.revision
+    }
+
+    /// Number of instruction address comparators.
+    pub fn comparators(&self) -> u8 {
+        self.comparators
+    }
+
+    /// Turn the whole unit on or off, the comparators keep their settings.
+    pub async fn enable(&mut self, enable: bool) -> Result<(), RequestError> {
+        let ctrl = FpCtrl::default().set_key(true).set_enable(enable);
+        self.memap.write_32(FpCtrl::ADDRESS, ctrl.into()).await
+    }
+
+    /// Break at `address`, returning the comparator used.
+    ///
+    /// The unit is enabled as well. On version 1 two breakpoints in the same
+    /// word share a comparator.
+    pub async fn set_breakpoint(&mut self, address: u32) -> Result<u8, BreakpointError> {
+        if self.revision == FpbRevision::V1 && address >= V1_CODE_LIMIT {
+            return Err(BreakpointError::UnsupportedAddress(address));
+        }
+        let comps = self.read_comparators().await?;
+        let (index, comp) = match self.find(&comps, address) {
+            Some(index) => (index, comps[index as usize]),
+            None => {
+                let index = comps
+                    .iter()
+                    .position(|comp| !comp.enable())
+                    .ok_or(BreakpointError::NoFreeComparator(self.comparators))?;
+                (index as u8, FpComp::default())
+            }
+        };
+        let comp = match self.revision {
+            FpbRevision::V1 => comp
+                .set_comp(address >> 2)
+                .set_replace(comp.replace() | replace(address)),
+            FpbRevision::V2 => comp.set_bpaddr(address >> 1),
+        };
+        self.write_comparator(index, comp.set_enable(true)).await?;
+        self.enable(true).await?;
+        Ok(index)
+    }
+
+    /// Remove the breakpoint at `address`.
+    pub async fn clear_breakpoint(&mut self, address: u32) -> Result<(), BreakpointError> {
+        let comps = self.read_comparators().await?;
+        let index = self
+            .find(&comps, address)
+            .ok_or(BreakpointError::NotSet(address))?;
+        let comp = comps[index as usize];
+        let comp = match self.revision {
+            FpbRevision::V1 if comp.replace() & replace(address) == 0 => {
+                return Err(BreakpointError::NotSet(address));
+            }
+            FpbRevision::V1 => match comp.replace() & !replace(address) {
+                0 => FpComp::default(),
+                rest => comp.set_replace(rest),
+            },
+            FpbRevision::V2 => FpComp::default(),
+        };
+        self.write_comparator(index, comp).await?;
+        Ok(())
+    }
+
+    /// Remove every breakpoint.
+    pub async fn clear_all(&mut self) -> Result<(), RequestError> {
+        for index in 0..self.comparators {
+            self.write_comparator(index, FpComp::default()).await?;
+        }
+        Ok(())
+    }
+
+    /// Addresses of all breakpoints, in comparator order.
+    pub async fn breakpoints(&mut self) -> Result<Vec<u32, MAX_BREAKPOINTS>, RequestError> {
+        let mut breakpoints = Vec::new();
+        for comp in self.read_comparators().await? {
+            if !comp.enable() {
+                continue;
+            }
+            match self.revision {
+                FpbRevision::V1 => {
+                    let word = comp.comp() << 2;
+                    for (bit, offset) in [(REPLACE_LOWER, 0), (REPLACE_UPPER, 2)] {
+                        if comp.replace() & bit != 0 {
+                            let _ = breakpoints.push(word | offset);
+                        }
+                    }
+                }
+                FpbRevision::V2 => {
+                    let _ = breakpoints.push(comp.bpaddr() << 1);
+                }
+            }
+        }
+        Ok(breakpoints)
+    }
+
+    /// Comparator with a breakpoint at `address`, or on version 1 at the
+    /// other halfword of the same word.
+    fn find(&self, comps: &[FpComp], address: u32) -> Option<u8> {
+        let position = comps.iter().position(|comp| {
+            comp.enable()
+                && match self.revision {
+                    FpbRevision::V1 => comp.comp() == (address >> 2) & 0x7ff_ffff,
+                    FpbRevision::V2 => comp.bpaddr() == address >> 1,
+                }
+        });
+        position.map(|index| index as u8)
+    }
+
+    async fn read_comparators(&mut self) -> Result<Vec<FpComp, MAX_COMPARATORS>, RequestError> {
+        let mut values = [0; MAX_COMPARATORS];
+        let values = &mut values[..self.comparators as usize];
+        self.memap.read_block(FP_COMP0, values).await?;
+        Ok(values.iter().map(|&value| FpComp::from(value)).collect())
+    }
+
+    async fn write_comparator(&mut self, index: u8, comp: FpComp) -> Result<(), RequestError> {
+        self.memap
+            .write_32(FP_COMP0 + 4 * index as u32, comp.into())
+            .await
+    }
+}
+
+/// Version 1 REPLACE bits breaking on the halfword at `address`.
+fn replace(address: u32) -> u8 {
+    match address & 0x2 {
+        0 => REPLACE_LOWER,
+        _ => REPLACE_UPPER,
+    }
+}
+
+#[cfg(all(test, feature = "sim"))]
+mod tests {
+    use super::*;
+    use crate::sim::cortexm::FPB_COMPARATORS;
+    use crate::sim::{block_on, CoreSim, MemApSim, Memory, SimIo, Target, IDCODE};
+
+    fn fpb_swd(revision: u8) -> Swd<SimIo<Target>> {
+        let mut core = CoreSim::new();
+        core.fpb_revision = revision;
+        let memory = Memory::new().with_core(core);
+        let target = Target::new(IDCODE).with_ap(MemApSim::new(0xe00ff003, memory));
+        Swd::new(SimIo::new(target))
+    }
+
+    fn core_sim(swd: &mut Swd<SimIo<Target>>) -> &mut CoreSim {
+        swd.io.target.aps[0].memory.core_mut().unwrap()
+    }
+
+    #[test]
+    fn v1_shared_comparator() {
+        let mut swd = fpb_swd(0);
+        block_on(async {
+            swd.reset().await.unwrap();
+            let mut fpb = swd.fpb(0).await.unwrap();
+            assert_eq!(fpb.revision(), FpbRevision::V1);
+            assert_eq!(fpb.set_breakpoint(0x0800_0100).await, Ok(0));
+            assert_eq!(fpb.set_breakpoint(0x0800_0102).await, Ok(0));
+            assert_eq!(fpb.breakpoints().await.unwrap(), [0x0800_0100, 0x0800_0102]);
+        });
+        let core = core_sim(&mut swd);
+        assert!(core.fpb_enable);
+        assert_eq!(core.fp_comp[0], 0xc800_0101);
+        assert_eq!(core.fp_comp[1], 0);
+
+        block_on(async {
+            let mut fpb = swd.fpb(0).await.unwrap();
+            fpb.clear_breakpoint(0x0800_0100).await.unwrap();
+            assert_eq!(fpb.breakpoints().await.unwrap(), [0x0800_0102]);
+            assert_eq!(
+                fpb.clear_breakpoint(0x0800_0100).await,
+                Err(BreakpointError::NotSet(0x0800_0100))
+            );
+        });
+        assert_eq!(core_sim(&mut swd).fp_comp[0], 0x8800_0101);
+
+        block_on(async {
+            let mut fpb = swd.fpb(0).await.unwrap();
+            fpb.clear_breakpoint(0x0800_0102).await.unwrap();
+            assert!(fpb.breakpoints().await.unwrap().is_empty());
+        });
+        assert_eq!(core_sim(&mut swd).fp_comp[0], 0);
+    }
+
+    #[test]
+    fn v1_code_region_only() {
+        let mut swd = fpb_swd(0);
+        block_on(async {
+            swd.reset().await.unwrap();
+            let mut fpb = swd.fpb(0).await.unwrap();
+            let err = fpb.set_breakpoint(0x2000_0000).await.unwrap_err();
+            assert_eq!(err, BreakpointError::UnsupportedAddress(0x2000_0000));
+            assert_eq!(u8::from(err), 0x21);
+            assert_eq!(fpb.set_breakpoint(0x1fff_fffe).await, Ok(0));
+        });
+    }
+
+    #[test]
+    fn v2() {
+        let mut swd = fpb_swd(1);
+        block_on(async {
+            swd.reset().await.unwrap();
+            let mut fpb = swd.fpb(0).await.unwrap();
+            assert_eq!(fpb.revision(), FpbRevision::V2);
+            assert_eq!(fpb.set_breakpoint(0x2000_0100).await, Ok(0));
+            // Halfwords of the same word need a comparator each.
+            assert_eq!(fpb.set_breakpoint(0x0800_0100).await, Ok(1));
+            assert_eq!(fpb.set_breakpoint(0x0800_0102).await, Ok(2));
+            assert_eq!(fpb.set_breakpoint(0x0800_0100).await, Ok(1));
+            assert_eq!(
+                fpb.breakpoints().await.unwrap(),
+                [0x2000_0100, 0x0800_0100, 0x0800_0102]
+            );
+            fpb.clear_breakpoint(0x2000_0100).await.unwrap();
+            assert_eq!(fpb.breakpoints().await.unwrap(), [0x0800_0100, 0x0800_0102]);
+        });
+        let core = core_sim(&mut swd);
+        assert!(core.fpb_enable);
+        assert_eq!(core.fp_comp[..3], [0, 0x0800_0101, 0x0800_0103]);
+    }
+
+    #[test]
+    fn no_free_comparator() {
+        let mut swd = fpb_swd(0);
+        block_on(async {
+            swd.reset().await.unwrap();
+            let mut fpb = swd.fpb(0).await.unwrap();
+            assert_eq!(fpb.comparators(), FPB_COMPARATORS as u8);
+            for index in 0..FPB_COMPARATORS as u8 {
+                let address = 0x0800_0000 + 4 * index as u32;
+                assert_eq!(fpb.set_breakpoint(address).await, Ok(index));
+            }
+            let err = fpb.set_breakpoint(0x0800_1000).await.unwrap_err();
+            assert_eq!(
+                err,
+                BreakpointError::NoFreeComparator(FPB_COMPARATORS as u8)
+            );
+            // The other halfword of a word in use still fits.
+            assert_eq!(fpb.set_breakpoint(0x0800_0002).await, Ok(0));
+            fpb.clear_all().await.unwrap();
+            assert_eq!(fpb.set_breakpoint(0x0800_1000).await, Ok(0));
+        });
+    }
+
+    #[test]
+    fn not_set() {
+        let mut swd = fpb_swd(1);
+        block_on(async {
+            swd.reset().await.unwrap();
+            let mut fpb = swd.fpb(0).await.unwrap();
+            assert_eq!(
+                fpb.clear_breakpoint(0x0800_0100).await,
+                Err(BreakpointError::NotSet(0x0800_0100))
+            );
+            fpb.set_breakpoint(0x0800_0100).await.unwrap();
+            let err = fpb.clear_breakpoint(0x0800_0102).await.unwrap_err();
+            assert_eq!(err, BreakpointError::NotSet(0x0800_0102));
+            assert_eq!(u8::from(err), 0x22);
+            assert_eq!(fpb.breakpoints().await.unwrap(), [0x0800_0100]);
+        });
+    }
+}
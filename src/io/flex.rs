@@ -0,0 +1,68 @@
+use esp_hal::gpio::{AnyPin, Flex, Pull};
+use esp_hal::peripheral::Peripheral;
+
+use super::{NResetPin, SwdIo, Unsupported};
+
+pub struct FlexIo<'a> {
+    pub swclk: Flex<'a>,
+    pub swdio: Flex<'a>,
+    pub nreset: Option<NResetPin<'a>>,
+}
+
+impl<'a> FlexIo<'a> {
+    pub fn new(
+        swclk: impl Peripheral<P = impl Into<AnyPin>> + 'a,
+        swdio: impl Peripheral<P = impl Into<AnyPin>> + 'a,
+    ) -> Self {
+        let mut swclk = Flex::new(swclk);
+        let mut swdio = Flex::new(swdio);
+        swclk.set_as_output();
+        swdio.set_as_output();
+        Self {
+            swclk,
+            swdio,
+            nreset: None,
+        }
+    }
+
+    pub fn with_nreset(mut self, nreset: impl Peripheral<P = impl Into<AnyPin>> + 'a) -> Self {
+        self.nreset = Some(NResetPin::new(nreset));
+        self
+    }
+}
+
+impl SwdIo for FlexIo<'_> {
+    fn set_swclk(&mut self, high: bool) {
+        self.swclk.set_level(high.into());
+    }
+
+    fn set_swdio(&mut self, high: bool) {
+        self.swdio.set_level(high.into());
+    }
+
+    fn swdio(&mut self) -> bool {
+        self.swdio.level().into()
+    }
+
+    fn swclk_as_output(&mut self) {
+        self.swclk.set_as_output();
+    }
+
+    fn swdio_as_output(&mut self) {
+        self.swdio.set_as_output();
+    }
+
+    fn swdio_as_input(&mut self) {
+        self.swdio.set_as_input(Pull::None);
+    }
+
+    fn set_nreset(&mut self, asserted: bool) -> Result<(), Unsupported> {
+        let nreset = self.nreset.as_mut().ok_or(Unsupported)?;
+        nreset.set_asserted(asserted);
+        Ok(())
+    }
+
+    fn nreset(&mut self) -> Result<bool, Unsupported> {
+        Ok(self.nreset.as_mut().ok_or(Unsupported)?.asserted())
+    }
+}
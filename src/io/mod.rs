@@ -0,0 +1,71 @@
+#[cfg(feature = "esp32c3")]
+pub mod flex;
+#[cfg(feature = "esp32c3")]
+pub use flex::FlexIo;
+
+#[cfg(feature = "esp32c3")]
+pub mod nreset;
+#[cfg(feature = "esp32c3")]
+pub use nreset::NResetPin;
+
+#[cfg(feature = "esp32c3")]
+pub mod spi;
+#[cfg(feature = "esp32c3")]
+pub use spi::SpiIo;
+
+/// The backend has no hardware shifter for the requested transfer.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Unsupported;
+
+/// Bit-level access to the SWCLK and SWDIO lines, and nRESET if there is one.
+///
+/// `Swd` only ever talks to the wire through this trait, so the protocol
+/// logic can run against real pins as well as a simulated target.
+pub trait SwdIo {
+    /// Drive SWCLK high or low.
+    fn set_swclk(&mut self, high: bool);
+
+    /// Set the level driven on SWDIO while it is an output.
+    fn set_swdio(&mut self, high: bool);
+
+    /// Sample the current level of SWDIO.
+    fn swdio(&mut self) -> bool;
+
+    /// Make SWCLK an output.
+    fn swclk_as_output(&mut self);
+
+    /// Let the host drive SWDIO.
+    fn swdio_as_output(&mut self);
+
+    /// Release SWDIO so the target can drive it.
+    fn swdio_as_input(&mut self);
+
+    /// Follow a change of the SWCLK frequency in Hz.
+    fn set_frequency(&mut self, _hz: u32) {}
+
+    /// Clock out the low `length` bits of `value`, LSB first, in hardware.
+    ///
+    /// Backends without a hardware shifter return `Unsupported` and the
+    /// caller bit-bangs the transfer through the methods above instead, so
+    /// it must only be returned before anything was clocked.
+    fn shift_out(&mut self, _value: u32, _length: usize) -> Result<(), Unsupported> {
+        Err(Unsupported)
+    }
+
+    /// Clock in `length` bits, LSB first, in hardware.
+    fn shift_in(&mut self, _length: usize) -> Result<u32, Unsupported> {
+        Err(Unsupported)
+    }
+
+    /// Pull the open-drain nRESET line low, or release it with `false`.
+    ///
+    /// Backends without an nRESET pin return `Unsupported`.
+    fn set_nreset(&mut self, _asserted: bool) -> Result<(), Unsupported> {
+        Err(Unsupported)
+    }
+
+    /// Sample nRESET, `true` while it is low, whoever pulls it.
+    fn nreset(&mut self) -> Result<bool, Unsupported> {
+        Err(Unsupported)
+    }
+}
@@ -0,0 +1,27 @@
+use esp_hal::gpio::{AnyPin, Flex, Level, Pull};
+use esp_hal::peripheral::Peripheral;
+
+/// Open-drain nRESET output that can also sense the line.
+///
+/// The pin only ever pulls low, so the target or another debugger can hold
+/// the line low as well and a reset supervisor is never fought.
+pub struct NResetPin<'a> {
+    pin: Flex<'a>,
+}
+
+impl<'a> NResetPin<'a> {
+    pub fn new(pin: impl Peripheral<P = impl Into<AnyPin>> + 'a) -> Self {
+        let mut pin = Flex::new(pin);
+        pin.set_level(Level::High);
+        pin.set_as_open_drain(Pull::Up);
+        Self { pin }
+    }
+
+    pub fn set_asserted(&mut self, asserted: bool) {
+        self.pin.set_level((!asserted).into());
+    }
+
+    pub fn asserted(&mut self) -> bool {
+        self.pin.level() == Level::Low
+    }
+}
@@ -0,0 +1,186 @@
+use core::fmt::Debug;
+
+use esp_hal::gpio::{AnyPin, Flex, Pull};
+use esp_hal::peripheral::Peripheral;
+use esp_hal::peripherals::SPI2;
+use esp_hal::spi::master::{Address, Command, Config, Spi};
+use esp_hal::spi::{BitOrder, DataMode, Mode};
+use esp_hal::Blocking;
+use fugit::HertzU32;
+use log::warn;
+
+use super::{NResetPin, SwdIo, Unsupported};
+
+/// SWD pins shifted by GPSPI2 in 3-wire half-duplex mode.
+///
+/// Whole bytes of request and data phases go through the SPI peripheral,
+/// while turnaround, ack and parity bits are bit-banged on the same pins.
+/// The pins are handed back and forth between the SPI signals and plain
+/// GPIO through the GPIO matrix around every hardware transfer.
+pub struct SpiIo<'a> {
+    swclk_pin: AnyPin,
+    swdio_pin: AnyPin,
+    swclk: Flex<'a>,
+    swdio: Flex<'a>,
+    swdio_output: bool,
+    spi: Option<Spi<'a, Blocking>>,
+    /// The peripheral runs at the current SWCLK frequency.
+    configured: bool,
+    pub nreset: Option<NResetPin<'a>>,
+}
+
+impl<'a> SpiIo<'a> {
+    pub fn new(spi: SPI2, swclk: AnyPin, swdio: AnyPin) -> Self {
+        let spi = Spi::new(spi, config(HertzU32::MHz(1))).unwrap();
+        let mut swclk_gpio = Flex::new(unsafe { swclk.clone_unchecked() });
+        let mut swdio_gpio = Flex::new(unsafe { swdio.clone_unchecked() });
+        swclk_gpio.set_as_output();
+        swdio_gpio.set_as_output();
+        Self {
+            swclk_pin: swclk,
+            swdio_pin: swdio,
+            swclk: swclk_gpio,
+            swdio: swdio_gpio,
+            swdio_output: true,
+            spi: Some(spi),
+            configured: true,
+            nreset: None,
+        }
+    }
+
+    pub fn with_nreset(mut self, nreset: impl Peripheral<P = impl Into<AnyPin>> + 'a) -> Self {
+        self.nreset = Some(NResetPin::new(nreset));
+        self
+    }
+
+    /// Route the pins to the SPI peripheral for one transfer.
+    ///
+    /// SWD targets change SWDIO after the rising edge and `Swd::swd_clock`
+    /// samples just before it, so reads and writes both use mode 0. The
+    /// turnaround, ack and parity bits around every transfer are bit-banged
+    /// on the same pins, so they go back to the GPIO matrix afterwards.
+    ///
+    /// `Unsupported` is only returned before anything is clocked, so `Swd`
+    /// can bit-bang the transfer instead. The esp-hal transfers check their
+    /// arguments before they start, if one fails anyway the bits already
+    /// went out and there is nothing to fall back to: the error is logged
+    /// and the ack or parity check of the SWD request reports it.
+    fn with_spi<E: Debug>(
+        &mut self,
+        f: impl FnOnce(&mut Spi<'a, Blocking>) -> Result<(), E>,
+    ) -> Result<(), Unsupported> {
+        if !self.configured {
+            return Err(Unsupported);
+        }
+        let spi = self.spi.take().ok_or(Unsupported)?;
+        let mut spi = unsafe {
+            spi.with_sck(self.swclk_pin.clone_unchecked())
+                .with_sio0(self.swdio_pin.clone_unchecked())
+        };
+        if let Err(err) = f(&mut spi) {
+            warn!("SPI transfer failed: {:?}", err);
+        }
+        self.spi = Some(spi);
+
+        // Hand the pins back to the GPIO matrix.
+        self.swclk.set_low();
+        self.swclk.set_as_output();
+        if self.swdio_output {
+            self.swdio.set_as_output();
+        } else {
+            self.swdio.set_as_input(Pull::None);
+        }
+        Ok(())
+    }
+}
+
+fn config(frequency: HertzU32) -> Config {
+    Config {
+        frequency,
+        mode: Mode::_0,
+        read_bit_order: BitOrder::LsbFirst,
+        write_bit_order: BitOrder::LsbFirst,
+        ..Config::default()
+    }
+}
+
+impl SwdIo for SpiIo<'_> {
+    fn set_swclk(&mut self, high: bool) {
+        self.swclk.set_level(high.into());
+    }
+
+    fn set_swdio(&mut self, high: bool) {
+        self.swdio.set_level(high.into());
+    }
+
+    fn swdio(&mut self) -> bool {
+        self.swdio.level().into()
+    }
+
+    fn swclk_as_output(&mut self) {
+        self.swclk.set_as_output();
+    }
+
+    fn swdio_as_output(&mut self) {
+        self.swdio_output = true;
+        self.swdio.set_as_output();
+    }
+
+    fn swdio_as_input(&mut self) {
+        self.swdio_output = false;
+        self.swdio.set_as_input(Pull::None);
+    }
+
+    /// Clock the SPI peripheral at `hz`, or leave every transfer to
+    /// bit-banging if it cannot run at that frequency.
+    fn set_frequency(&mut self, hz: u32) {
+        let config = config(HertzU32::Hz(hz.max(1)));
+        self.configured = match self.spi.as_mut() {
+            Some(spi) => spi.apply_config(&config).is_ok(),
+            None => false,
+        };
+    }
+
+    fn shift_out(&mut self, value: u32, length: usize) -> Result<(), Unsupported> {
+        if length == 0 || length > 32 || length % 8 != 0 || !self.swdio_output {
+            return Err(Unsupported);
+        }
+        let bytes = value.to_le_bytes();
+        self.with_spi(|spi| {
+            spi.half_duplex_write(
+                DataMode::Single,
+                Command::None,
+                Address::None,
+                0,
+                &bytes[..length / 8],
+            )
+        })
+    }
+
+    fn shift_in(&mut self, length: usize) -> Result<u32, Unsupported> {
+        if length == 0 || length > 32 || length % 8 != 0 || self.swdio_output {
+            return Err(Unsupported);
+        }
+        let mut bytes = [0u8; 4];
+        self.with_spi(|spi| {
+            spi.half_duplex_read(
+                DataMode::Single,
+                Command::None,
+                Address::None,
+                0,
+                &mut bytes[..length / 8],
+            )
+        })?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn set_nreset(&mut self, asserted: bool) -> Result<(), Unsupported> {
+        let nreset = self.nreset.as_mut().ok_or(Unsupported)?;
+        nreset.set_asserted(asserted);
+        Ok(())
+    }
+
+    fn nreset(&mut self) -> Result<bool, Unsupported> {
+        Ok(self.nreset.as_mut().ok_or(Unsupported)?.asserted())
+    }
+}
@@ -0,0 +1,127 @@
+//! JEP106 manufacturer codes.
+//!
+//! Designers are encoded like IDR.DESIGNER and the PIDR designer fields, with
+//! the continuation count in bits 10:7 and the identity code, without its
+//! parity bit, in bits 6:0.
+
+use core::fmt;
+
+/// A JEP106 manufacturer code.
+#[derive(Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Jep106(pub u16);
+
+pub const ARM: Jep106 = Jep106::new(4, 0x3b);
+pub const NORDIC: Jep106 = Jep106::new(2, 0x44);
+pub const ST: Jep106 = Jep106::new(0, 0x20);
+pub const RASPBERRY_PI: Jep106 = Jep106::new(9, 0x13);
+pub const ESPRESSIF: Jep106 = Jep106::new(12, 0x12);
+
+/// Manufacturers by continuation count, then identity code.
+const NAMES: &[(Jep106, &str)] = &[
+    (Jep106::new(0, 0x01), "AMD"),
+    (Jep106::new(0, 0x04), "Fujitsu"),
+    (Jep106::new(0, 0x07), "Hitachi"),
+    (Jep106::new(0, 0x09), "Intel"),
+    (Jep106::new(0, 0x0e), "Freescale"),
+    (Jep106::new(0, 0x10), "NEC"),
+    (Jep106::new(0, 0x15), "NXP"),
+    (Jep106::new(0, 0x17), "Texas Instruments"),
+    (Jep106::new(0, 0x18), "Toshiba"),
+    (Jep106::new(0, 0x1c), "Mitsubishi"),
+    (Jep106::new(0, 0x1f), "Atmel"),
+    (ST, "STMicroelectronics"),
+    (Jep106::new(0, 0x21), "Lattice"),
+    (Jep106::new(0, 0x29), "Microchip"),
+    (Jep106::new(0, 0x2c), "Micron"),
+    (Jep106::new(0, 0x34), "Cypress"),
+    (Jep106::new(0, 0x41), "Infineon"),
+    (Jep106::new(0, 0x49), "Xilinx"),
+    (Jep106::new(0, 0x4e), "Samsung"),
+    (Jep106::new(0, 0x65), "Analog Devices"),
+    (Jep106::new(0, 0x6e), "Altera"),
+    (NORDIC, "Nordic Semiconductor"),
+    (ARM, "ARM"),
+    (Jep106::new(9, 0x09), "SiFive"),
+    (RASPBERRY_PI, "Raspberry Pi"),
+    (ESPRESSIF, "Espressif"),
+];
+
+impl Jep106 {
+    pub const fn new(continuation: u8, identity: u8) -> Self {
+        Self(((continuation as u16 & 0xf) << 7) | (identity as u16 & 0x7f))
+    }
+
+    /// Number of 0x7f continuation codes before the identity code.
+    pub fn continuation(&self) -> u8 {
+        (self.0 >> 7) as u8 & 0xf
+    }
+
+    pub fn identity(&self) -> u8 {
+        self.0 as u8 & 0x7f
+    }
+
+    /// Name of the manufacturer, if it is a known one.
+    pub fn name(&self) -> Option<&'static str> {
+        NAMES
+            .iter()
+            .find(|(code, _)| code == self)
+            .map(|(_, name)| *name)
+    }
+}
+
+impl From<u32> for Jep106 {
+    fn from(value: u32) -> Self {
+        Self(value as u16 & 0x7ff)
+    }
+}
+
+impl From<Jep106> for u32 {
+    fn from(value: Jep106) -> Self {
+        value.0.into()
+    }
+}
+
+impl fmt::Debug for Jep106 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "Jep106({:#05x}, {:?})", self.0, name),
+            None => write!(f, "Jep106({:#05x})", self.0),
+        }
+    }
+}
+
+impl fmt::Display for Jep106 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => f.write_str(name),
+            None => write!(f, "JEP106 {}/{:#04x}", self.continuation(), self.identity()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::format;
+
+    use super::*;
+
+    #[test]
+    fn arm() {
+        assert_eq!(ARM.continuation(), 4);
+        assert_eq!(ARM.identity(), 0x3b);
+        assert_eq!(u32::from(ARM), 0x23b);
+        // IDR.DESIGNER and DPIDR.DESIGNER hold the same 11 bits.
+        assert_eq!(Jep106::from(0x477_u32 >> 1), ARM);
+        assert_eq!(ARM.name(), Some("ARM"));
+        assert_eq!(format!("{}", ARM), "ARM");
+        assert_eq!(format!("{:?}", ARM), "Jep106(0x23b, \"ARM\")");
+    }
+
+    #[test]
+    fn unknown() {
+        let code = Jep106::new(3, 0x55);
+        assert_eq!(code.name(), None);
+        assert_eq!(format!("{}", code), "JEP106 3/0x55");
+        assert_eq!(format!("{:?}", code), "Jep106(0x1d5)");
+    }
+}
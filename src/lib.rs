@@ -1,5 +1,12 @@
 #![no_std]
 
+#[cfg(test)]
+extern crate std;
+
+#[cfg(feature = "sim")]
+extern crate alloc;
+
+#[cfg(feature = "esp32c3")]
 macro_rules! mk_static {
     ($t:ty,$val:expr) => {{
         static STATIC_CELL: static_cell::StaticCell<$t> = static_cell::StaticCell::new();
@@ -9,13 +16,28 @@ macro_rules! mk_static {
     }};
 }
 
+#[cfg(feature = "esp32c3")]
 pub(crate) use mk_static;
 
+pub mod ap;
+pub mod clock;
+pub mod cortexm;
+pub mod fpb;
+pub mod io;
+pub mod jep106;
 pub mod memap;
+pub mod nreset;
+pub mod parts;
 pub mod registers;
+pub mod romtable;
 pub mod swd;
 
+#[cfg(feature = "sim")]
+pub mod sim;
+
+#[cfg(feature = "esp32c3")]
 pub mod net;
+#[cfg(feature = "esp32c3")]
 pub mod wifi;
 
 pub(crate) use registers::make_register;
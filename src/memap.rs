@@ -1,23 +1,47 @@
 use crate::{
+    ap::ApAddress,
+    io::SwdIo,
     registers::ap::{
-        memap::{Base, Drw, Tar},
+        memap::{AddrInc, Base, Drw, Size, Tar, CSW},
         ReadRegister, WriteRegister,
     },
     swd::{RequestError, Swd},
 };
 
-pub struct MemAp<'swd, 'pins> {
-    swd: &'swd mut Swd<'pins>,
-    ap: u8,
+pub struct MemAp<'swd, Io> {
+    swd: &'swd mut Swd<Io>,
+    ap: ApAddress,
+    /// Last value written to CSW, if known.
+    csw: Option<CSW>,
+    /// Whether byte and halfword accesses are implemented, once probed.
+    sub_word: Option<bool>,
+    /// Whether packed transfers are implemented, once probed.
+    packed: Option<bool>,
 }
 
-impl<'pins> Swd<'pins> {
-    pub fn memap<'swd>(&'swd mut self, ap: u8) -> MemAp<'swd, 'pins> {
-        MemAp { swd: self, ap }
+impl<Io: SwdIo> Swd<Io> {
+    pub fn memap(&mut self, ap: impl Into<ApAddress>) -> MemAp<'_, Io> {
+        MemAp {
+            swd: self,
+            ap: ap.into(),
+            csw: None,
+            sub_word: None,
+            packed: None,
+        }
     }
 }
 
-impl MemAp<'_, '_> {
+impl<Io: SwdIo> MemAp<'_, Io> {
+    /// The SWD connection the MEM-AP is reached through.
+    pub fn swd(&mut self) -> &mut Swd<Io> {
+        self.swd
+    }
+
+    /// Forget the cached CSW, after something may have reset the AP.
+    pub fn invalidate(&mut self) {
+        self.csw = None;
+    }
+
     pub async fn write_register<Reg: WriteRegister>(
         &mut self,
         reg: Reg,
@@ -43,15 +67,464 @@ impl MemAp<'_, '_> {
     }
 
     pub async fn read_32(&mut self, address: u32) -> Result<u32, RequestError> {
+        self.set_transfer(Size::Word, AddrInc::Off).await?;
         self.write_register(Tar::default().set_address(address))
             .await?;
         Ok(self.read_register::<Drw>().await?.data())
     }
 
+    /// Set the transfer size and address increment mode in CSW, skipping the
+    /// write if CSW already holds them.
+    pub async fn set_transfer(&mut self, size: Size, addrinc: AddrInc) -> Result<(), RequestError> {
+        let csw = match self.csw {
+            Some(csw) => csw,
+            None => self.read_register::<CSW>().await?,
+        };
+        let new_csw = csw.set_size(size).set_addrinc(addrinc);
+        self.csw = None;
+        if new_csw != csw {
+            self.write_register(new_csw).await?;
+        }
+        self.csw = Some(new_csw);
+        Ok(())
+    }
+
+    /// Write a modified CSW, read back what the MEM-AP accepted and restore it.
+    async fn probe_csw(&mut self, f: impl FnOnce(CSW) -> CSW) -> Result<CSW, RequestError> {
+        let old_csw = self.read_register::<CSW>().await?;
+        self.csw = None;
+        self.write_register(f(old_csw)).await?;
+        let csw = self.read_register::<CSW>().await?;
+        self.write_register(old_csw).await?;
+        self.csw = Some(old_csw);
+        Ok(csw)
+    }
+
+    /// Probe whether the MEM-AP implements `size` by writing it to CSW and
+    /// reading it back.
+    pub async fn supports_size(&mut self, size: Size) -> Result<bool, RequestError> {
+        let csw = self.probe_csw(|csw| csw.set_size(size)).await?;
+        Ok(csw.size() == size)
+    }
+
+    /// Probe whether the MEM-AP implements packed transfers.
+    pub async fn supports_packed(&mut self) -> Result<bool, RequestError> {
+        if let Some(packed) = self.packed {
+            return Ok(packed);
+        }
+        let packed = self.sub_word().await?
+            && self
+                .probe_csw(|csw| csw.set_size(Size::Byte).set_addrinc(AddrInc::Packed))
+                .await?
+                .addrinc()
+                == AddrInc::Packed;
+        self.packed = Some(packed);
+        Ok(packed)
+    }
+
+    /// Byte and halfword accesses are either both implemented or both not.
+    async fn sub_word(&mut self) -> Result<bool, RequestError> {
+        if let Some(sub_word) = self.sub_word {
+            return Ok(sub_word);
+        }
+        let sub_word = self.supports_size(Size::Byte).await?;
+        self.sub_word = Some(sub_word);
+        Ok(sub_word)
+    }
+
+    async fn read_sized(&mut self, address: u32, size: Size) -> Result<u32, RequestError> {
+        self.set_transfer(size, AddrInc::Off).await?;
+        self.write_register(Tar::default().set_address(address))
+            .await?;
+        Ok(self.read_register::<Drw>().await?.data())
+    }
+
+    async fn write_sized(
+        &mut self,
+        address: u32,
+        size: Size,
+        value: u32,
+    ) -> Result<(), RequestError> {
+        self.set_transfer(size, AddrInc::Off).await?;
+        self.write_register(Tar::default().set_address(address))
+            .await?;
+        self.write_register(Drw::default().set_data(value)).await
+    }
+
+    pub async fn read_8(&mut self, address: u32) -> Result<u8, RequestError> {
+        let shift = (address & 0x3) * 8;
+        let value = match self.sub_word().await? {
+            true => self.read_sized(address, Size::Byte).await?,
+            false => self.read_32(address & !0x3).await?,
+        };
+        Ok((value >> shift) as u8)
+    }
+
+    pub async fn read_16(&mut self, address: u32) -> Result<u16, RequestError> {
+        if address & 0x1 != 0 {
+            return Err(RequestError::Unaligned(address));
+        }
+        let shift = (address & 0x2) * 8;
+        let value = match self.sub_word().await? {
+            true => self.read_sized(address, Size::HalfWord).await?,
+            false => self.read_32(address & !0x3).await?,
+        };
+        Ok((value >> shift) as u16)
+    }
+
+    pub async fn write_8(&mut self, address: u32, value: u8) -> Result<(), RequestError> {
+        let shift = (address & 0x3) * 8;
+        if self.sub_word().await? {
+            return self
+                .write_sized(address, Size::Byte, (value as u32) << shift)
+                .await;
+        }
+        let word = self.read_32(address & !0x3).await?;
+        let word = (word & !(0xff << shift)) | ((value as u32) << shift);
+        self.write_32(address & !0x3, word).await
+    }
+
+    pub async fn write_16(&mut self, address: u32, value: u16) -> Result<(), RequestError> {
+        if address & 0x1 != 0 {
+            return Err(RequestError::Unaligned(address));
+        }
+        let shift = (address & 0x2) * 8;
+        if self.sub_word().await? {
+            return self
+                .write_sized(address, Size::HalfWord, (value as u32) << shift)
+                .await;
+        }
+        let word = self.read_32(address & !0x3).await?;
+        let word = (word & !(0xffff << shift)) | ((value as u32) << shift);
+        self.write_32(address & !0x3, word).await
+    }
+
+    /// Read arbitrary bytes, using the widest access the alignment allows.
+    pub async fn read_bytes(&mut self, address: u32, data: &mut [u8]) -> Result<(), RequestError> {
+        let mut address = address;
+        let mut data = data;
+        while !data.is_empty() {
+            let len = if address & 0x3 == 0 && data.len() >= 4 {
+                let mut words = [0u32; 64];
+                let words = &mut words[..(data.len() / 4).min(64)];
+                self.read_block(address, words).await?;
+                for (chunk, word) in data.as_chunks_mut::<4>().0.iter_mut().zip(words.iter()) {
+                    *chunk = word.to_le_bytes();
+                }
+                words.len() * 4
+            } else if self.sub_word().await? {
+                let len = ((4 - (address & 0x3)) as usize).min(data.len());
+                self.read_packed(address, Size::Byte, &mut data[..len])
+                    .await?;
+                len
+            } else {
+                data[0] = self.read_8(address).await?;
+                1
+            };
+            address = address.wrapping_add(len as u32);
+            data = &mut data[len..];
+        }
+        Ok(())
+    }
+
+    /// Write arbitrary bytes, using the widest access the alignment allows.
+    pub async fn write_bytes(&mut self, address: u32, data: &[u8]) -> Result<(), RequestError> {
+        let mut address = address;
+        let mut data = data;
+        while !data.is_empty() {
+            let len = if address & 0x3 == 0 && data.len() >= 4 {
+                let mut words = [0u32; 64];
+                let words = &mut words[..(data.len() / 4).min(64)];
+                for (word, chunk) in words.iter_mut().zip(data.as_chunks::<4>().0) {
+                    *word = u32::from_le_bytes(*chunk);
+                }
+                self.write_block(address, words).await?;
+                words.len() * 4
+            } else if self.sub_word().await? {
+                let len = ((4 - (address & 0x3)) as usize).min(data.len());
+                self.write_packed(address, Size::Byte, &data[..len]).await?;
+                len
+            } else {
+                self.write_8(address, data[0]).await?;
+                1
+            };
+            address = address.wrapping_add(len as u32);
+            data = &data[len..];
+        }
+        Ok(())
+    }
+
+    /// Read bytes with `size` wide accesses, on a MEM-AP that implements
+    /// them, see `sub_word`.
+    ///
+    /// When the MEM-AP implements packed transfers, every DRW access moves a
+    /// whole word of data. `address` and the length of `data` must be
+    /// multiples of `size`.
+    async fn read_packed(
+        &mut self,
+        address: u32,
+        size: Size,
+        data: &mut [u8],
+    ) -> Result<(), RequestError> {
+        let mut address = address;
+        let mut data = data;
+        while !data.is_empty() {
+            let (len, per_access) = self.packed_chunk(address, size, data.len()).await?;
+            let mut words = [0u32; 64];
+            let words = &mut words[..len / per_access];
+            self.write_register(Tar::default().set_address(address))
+                .await?;
+            self.read_drw(words).await?;
+            for (i, byte) in data[..len].iter_mut().enumerate() {
+                let lane = (address as usize + i) & 0x3;
+                *byte = (words[i / per_access] >> (lane * 8)) as u8;
+            }
+            address = address.wrapping_add(len as u32);
+            data = &mut data[len..];
+        }
+        Ok(())
+    }
+
+    /// Write bytes with `size` wide accesses, see `read_packed`.
+    async fn write_packed(
+        &mut self,
+        address: u32,
+        size: Size,
+        data: &[u8],
+    ) -> Result<(), RequestError> {
+        let mut address = address;
+        let mut data = data;
+        while !data.is_empty() {
+            let (len, per_access) = self.packed_chunk(address, size, data.len()).await?;
+            let mut words = [0u32; 64];
+            let words = &mut words[..len / per_access];
+            for (i, &byte) in data[..len].iter().enumerate() {
+                let lane = (address as usize + i) & 0x3;
+                words[i / per_access] |= (byte as u32) << (lane * 8);
+            }
+            self.write_register(Tar::default().set_address(address))
+                .await?;
+            self.write_drw(words).await?;
+            address = address.wrapping_add(len as u32);
+            data = &data[len..];
+        }
+        Ok(())
+    }
+
+    /// Set up CSW for the next run of a packed transfer and return its length
+    /// in bytes and the number of bytes moved per DRW access.
+    ///
+    /// Packed runs start word aligned and stay within a 1 KiB block, anything
+    /// else goes one access per DRW up to the next word boundary. Support for
+    /// packed transfers is only probed once a run could use them.
+    async fn packed_chunk(
+        &mut self,
+        address: u32,
+        size: Size,
+        len: usize,
+    ) -> Result<(usize, usize), RequestError> {
+        if address & 0x3 == 0 && len >= 4 && self.supports_packed().await? {
+            self.set_transfer(size, AddrInc::Packed).await?;
+            let block = (0x400 - (address & 0x3ff)) as usize;
+            Ok((len.min(block).min(64 * 4) & !0x3, 4))
+        } else {
+            self.set_transfer(size, AddrInc::Single).await?;
+            let len = ((4 - (address & 0x3)) as usize).min(len);
+            Ok((len, size.bytes() as usize))
+        }
+    }
+
+    /// Read words starting at the word aligned `address`.
+    ///
+    /// TAR auto-increment is only guaranteed within a 1 KiB block, so TAR is
+    /// written again at every 1 KiB boundary.
+    pub async fn read_block(
+        &mut self,
+        address: u32,
+        values: &mut [u32],
+    ) -> Result<(), RequestError> {
+        self.set_transfer(Size::Word, AddrInc::Single).await?;
+        let mut address = address;
+        let mut values = values;
+        while !values.is_empty() {
+            let (chunk, rest) = values.split_at_mut(block_words(address, values.len()));
+            self.write_register(Tar::default().set_address(address))
+                .await?;
+            self.read_drw(chunk).await?;
+            address = address.wrapping_add(chunk.len() as u32 * 4);
+            values = rest;
+        }
+        Ok(())
+    }
+
+    /// Write words starting at the word aligned `address`.
+    pub async fn write_block(&mut self, address: u32, values: &[u32]) -> Result<(), RequestError> {
+        self.set_transfer(Size::Word, AddrInc::Single).await?;
+        let mut address = address;
+        let mut values = values;
+        while !values.is_empty() {
+            let (chunk, rest) = values.split_at(block_words(address, values.len()));
+            self.write_register(Tar::default().set_address(address))
+                .await?;
+            self.write_drw(chunk).await?;
+            address = address.wrapping_add(chunk.len() as u32 * 4);
+            values = rest;
+        }
+        Ok(())
+    }
+
+    /// Write DRW once per value, streaming memory to TAR when CSW
+    /// auto-increment is enabled.
+    pub async fn write_drw(&mut self, values: &[u32]) -> Result<(), RequestError> {
+        self.swd
+            .write_ap_repeated(self.ap, self.ap.register::<Drw>(), values)
+            .await
+    }
+
+    /// Read DRW back to back with posted reads, streaming memory from TAR
+    /// when CSW auto-increment is enabled.
+    pub async fn read_drw(&mut self, values: &mut [u32]) -> Result<(), RequestError> {
+        self.swd
+            .read_ap_repeated(self.ap, self.ap.register::<Drw>(), values)
+            .await
+    }
+
     pub async fn write_32(&mut self, address: u32, value: u32) -> Result<(), RequestError> {
+        self.set_transfer(Size::Word, AddrInc::Off).await?;
         self.write_register(Tar::default().set_address(address))
             .await?;
         self.write_register(Drw::default().set_data(value)).await?;
         Ok(())
     }
 }
+
+/// Number of words, at most `len`, left before the next 1 KiB boundary.
+fn block_words(address: u32, len: usize) -> usize {
+    (((0x400 - (address & 0x3ff)) / 4) as usize).min(len)
+}
+
+#[cfg(all(test, feature = "sim"))]
+mod tests {
+    use crate::registers::ap::memap::Size;
+    use crate::sim::{block_on, swd, MemApSim, Memory, SimIo, Target, IDCODE};
+    use crate::swd::{RequestError, Swd};
+
+    #[test]
+    fn words() {
+        let mut swd = swd();
+        block_on(async {
+            swd.reset().await.unwrap();
+            let mut memap = swd.memap(0);
+            memap.write_32(0x2000_0000, 0x1234_5678).await.unwrap();
+            assert_eq!(memap.read_32(0x2000_0000).await, Ok(0x1234_5678));
+        });
+        let memory = &mut swd.io.target.aps[0].memory;
+        assert_eq!(memory.read_word(0x2000_0000), Ok(0x1234_5678));
+    }
+
+    #[test]
+    fn block_across_1k_boundary() {
+        let mut swd = swd();
+        let values: [u32; 8] = core::array::from_fn(|i| 0x100 * i as u32);
+        block_on(async {
+            swd.reset().await.unwrap();
+            let mut memap = swd.memap(0);
+            memap.write_block(0x2000_03f0, &values).await.unwrap();
+            let mut read = [0; 8];
+            memap.read_block(0x2000_03f0, &mut read).await.unwrap();
+            assert_eq!(read, values);
+        });
+        let memory = &mut swd.io.target.aps[0].memory;
+        assert_eq!(memory.read_word(0x2000_040c), Ok(0x700));
+    }
+
+    #[test]
+    fn bytes() {
+        let mut swd = swd();
+        let data: [u8; 11] = core::array::from_fn(|i| i as u8 + 1);
+        block_on(async {
+            swd.reset().await.unwrap();
+            let mut memap = swd.memap(0);
+            memap.write_bytes(0x2000_0001, &data).await.unwrap();
+            let mut read = [0; 13];
+            memap.read_bytes(0x2000_0000, &mut read).await.unwrap();
+            assert_eq!(read[0], 0);
+            assert_eq!(read[1..12], data);
+            assert_eq!(read[12], 0);
+            // Unaligned heads and tails never need packed transfers.
+            assert_eq!(memap.packed, None);
+        });
+    }
+
+    #[test]
+    fn halfwords() {
+        let mut swd = swd();
+        block_on(async {
+            swd.reset().await.unwrap();
+            let mut memap = swd.memap(0);
+            memap.write_16(0x2000_0002, 0xbeef).await.unwrap();
+            assert_eq!(memap.read_16(0x2000_0002).await, Ok(0xbeef));
+            assert_eq!(
+                memap.write_16(0x2000_0001, 0xbeef).await,
+                Err(RequestError::Unaligned(0x2000_0001))
+            );
+            assert_eq!(
+                memap.read_16(0x2000_0003).await,
+                Err(RequestError::Unaligned(0x2000_0003))
+            );
+        });
+        let memory = &mut swd.io.target.aps[0].memory;
+        assert_eq!(memory.read_word(0x2000_0000), Ok(0xbeef_0000));
+    }
+
+    #[test]
+    fn word_only() {
+        let mut memap = MemApSim::new(0xe00ff003, Memory::new());
+        memap.sub_word = false;
+        memap.packed = false;
+        memap.memory.write_word(0x2000_0000, 0x4433_2211).unwrap();
+        let mut swd = Swd::new(SimIo::new(Target::new(IDCODE).with_ap(memap)));
+        let data: [u8; 6] = core::array::from_fn(|i| 0xa0 + i as u8);
+        block_on(async {
+            swd.reset().await.unwrap();
+            let mut memap = swd.memap(0);
+            assert_eq!(memap.read_8(0x2000_0001).await, Ok(0x22));
+            assert_eq!(memap.read_16(0x2000_0002).await, Ok(0x4433));
+            memap.write_8(0x2000_0001, 0x55).await.unwrap();
+            memap.write_16(0x2000_0002, 0x7766).await.unwrap();
+            assert_eq!(memap.read_32(0x2000_0000).await, Ok(0x7766_5511));
+
+            memap.write_bytes(0x2000_0005, &data).await.unwrap();
+            let mut read = [0; 6];
+            memap.read_bytes(0x2000_0005, &mut read).await.unwrap();
+            assert_eq!(read, data);
+            assert_eq!(memap.packed, None);
+        });
+        let memory = &mut swd.io.target.aps[0].memory;
+        assert_eq!(memory.read_word(0x2000_0004), Ok(0xa2a1_a000));
+        assert_eq!(memory.read_word(0x2000_0008), Ok(0x00a5_a4a3));
+    }
+
+    #[test]
+    fn packed() {
+        let mut swd = swd();
+        let data: [u8; 10] = core::array::from_fn(|i| 0x10 + i as u8);
+        block_on(async {
+            swd.reset().await.unwrap();
+            let mut memap = swd.memap(0);
+            memap
+                .write_packed(0x2000_0002, Size::HalfWord, &data)
+                .await
+                .unwrap();
+            assert_eq!(memap.packed, Some(true));
+            let mut read = [0; 10];
+            memap
+                .read_packed(0x2000_0002, Size::HalfWord, &mut read)
+                .await
+                .unwrap();
+            assert_eq!(read, data);
+        });
+        let memory = &mut swd.io.target.aps[0].memory;
+        assert_eq!(memory.read_word(0x2000_0004), Ok(0x1514_1312));
+    }
+}
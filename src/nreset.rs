@@ -0,0 +1,85 @@
+//! Hardware reset through the open-drain nRESET line.
+
+use embassy_time::{Duration, Instant, Timer};
+use log::info;
+use thiserror::Error;
+
+use crate::io::SwdIo;
+use crate::registers::dp::CtrlStat;
+use crate::swd::{RequestError, Swd};
+
+/// How long `pulse_reset` holds nRESET low by default.
+pub const DEFAULT_RESET_PULSE: Duration = Duration::from_millis(10);
+/// How long nRESET may stay low after being released, for reset supervisors
+/// and slow RC pull-ups.
+pub const RESET_RELEASE_TIMEOUT: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Copy, Clone, Error, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ResetError {
+    #[error("{0}")]
+    Request(#[from] RequestError),
+    #[error("No nRESET pin")]
+    NoResetPin,
+}
+
+/// Error codes of the network protocol, next to those of `RequestError`.
+impl From<ResetError> for u8 {
+    fn from(value: ResetError) -> Self {
+        match value {
+            ResetError::Request(err) => err.into(),
+            ResetError::NoResetPin => 0x06,
+        }
+    }
+}
+
+impl<Io: SwdIo> Swd<Io> {
+    pub fn assert_reset(&mut self) -> Result<(), ResetError> {
+        self.io.set_nreset(true).map_err(|_| ResetError::NoResetPin)
+    }
+
+    /// Release nRESET and wait for the line to go high.
+    ///
+    /// Something else holding the line low past `RESET_RELEASE_TIMEOUT` is
+    /// only logged, the target stays in reset until it lets go.
+    pub async fn deassert_reset(&mut self) -> Result<(), ResetError> {
+        self.io
+            .set_nreset(false)
+            .map_err(|_| ResetError::NoResetPin)?;
+        let start = Instant::now();
+        while self.reset_asserted()? {
+            if start.elapsed() >= RESET_RELEASE_TIMEOUT {
+                info!("nRESET is held low by the target");
+                break;
+            }
+            Timer::after_micros(100).await;
+        }
+        Ok(())
+    }
+
+    /// Whether nRESET is low, driven by the probe or anything else.
+    pub fn reset_asserted(&mut self) -> Result<bool, ResetError> {
+        self.io.nreset().map_err(|_| ResetError::NoResetPin)
+    }
+
+    /// Hold nRESET low for `duration` and release it again.
+    pub async fn pulse_reset(&mut self, duration: Duration) -> Result<(), ResetError> {
+        self.assert_reset()?;
+        Timer::after(duration).await;
+        self.deassert_reset().await
+    }
+
+    /// Connect to the target: wake up the DP, clear sticky flags and power up
+    /// the debug domains.
+    ///
+    /// With `under_reset`, nRESET is asserted first and left asserted, so
+    /// firmware that reconfigures the SWD pins or sleeps never gets to run.
+    /// Release it with `deassert_reset`, or with `CortexM::release_reset` to
+    /// halt the core at the reset vector.
+    pub async fn attach(&mut self, under_reset: bool) -> Result<CtrlStat, ResetError> {
+        if under_reset {
+            self.assert_reset()?;
+            Timer::after(DEFAULT_RESET_PULSE).await;
+        }
+        Ok(self.reconnect().await?)
+    }
+}
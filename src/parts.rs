@@ -0,0 +1,144 @@
+//! Known ARM debug port, access port and CoreSight component part numbers.
+
+use crate::jep106::{self, Jep106};
+
+/// DPIDR.PARTNO of ARM debug ports.
+const DPS: &[(u8, &str)] = &[
+    (0xba, "SW-DP"),
+    (0xbb, "SW-DP (Cortex-M0)"),
+    (0xbc, "SW-DP (Cortex-M0+)"),
+];
+
+/// ARM AP IDR values with the revision field cleared.
+const APS: &[(u32, &str)] = &[
+    (0x0477_0001, "AHB-AP"),
+    (0x0477_0011, "AHB-AP (Cortex-M3/M4)"),
+    (0x0477_0021, "AHB-AP (Cortex-M0)"),
+    (0x0477_0031, "AHB-AP (Cortex-M0+)"),
+    (0x0477_0002, "APB-AP"),
+    (0x0477_0004, "AXI-AP"),
+    (0x0477_0005, "AHB5-AP"),
+    (0x0476_0010, "JTAG-AP"),
+];
+
+/// PIDR part numbers of ARM CoreSight and Cortex-M components. ARMv8-M
+/// components share the part number of their core.
+const COMPONENTS: &[(u16, &str)] = &[
+    (0x000, "Cortex-M3 SCS"),
+    (0x001, "Cortex-M3 ITM"),
+    (0x002, "Cortex-M3 DWT"),
+    (0x003, "Cortex-M3 FPB"),
+    (0x008, "Cortex-M0 SCS"),
+    (0x00a, "Cortex-M0 DWT"),
+    (0x00b, "Cortex-M0 BPU"),
+    (0x00c, "Cortex-M4 SCS"),
+    (0x00e, "Cortex-M7 FPB"),
+    (0x471, "Cortex-M0 ROM"),
+    (0x4c0, "Cortex-M0+ ROM"),
+    (0x4c3, "Cortex-M3 ROM"),
+    (0x4c4, "Cortex-M4 ROM"),
+    (0x4c7, "Cortex-M7 PPB ROM"),
+    (0x4c8, "Cortex-M7 ROM"),
+    (0x906, "CoreSight CTI"),
+    (0x907, "CoreSight ETB"),
+    (0x908, "CoreSight Trace Funnel"),
+    (0x912, "CoreSight TPIU"),
+    (0x913, "CoreSight ITM"),
+    (0x914, "CoreSight SWO"),
+    (0x923, "Cortex-M3 TPIU"),
+    (0x924, "Cortex-M3 ETM"),
+    (0x925, "Cortex-M4 ETM"),
+    (0x975, "Cortex-M7 ETM"),
+    (0x9a1, "Cortex-M4 TPIU"),
+    (0x9a9, "Cortex-M7 TPIU"),
+    (0xd20, "Cortex-M23"),
+    (0xd21, "Cortex-M33"),
+];
+
+fn lookup<K: PartialEq>(table: &[(K, &'static str)], key: K) -> Option<&'static str> {
+    table.iter().find(|(k, _)| *k == key).map(|(_, name)| *name)
+}
+
+/// Name of a debug port from DPIDR.DESIGNER and DPIDR.PARTNO.
+pub fn dp_name(designer: Jep106, partno: u8) -> Option<&'static str> {
+    (designer == jep106::ARM)
+        .then(|| lookup(DPS, partno))
+        .flatten()
+}
+
+/// Name of an access port from its IDR.
+pub fn ap_name(idr: u32) -> Option<&'static str> {
+    lookup(APS, idr & 0x0fff_ffff)
+}
+
+/// Name of a component from its PIDR designer and part number.
+pub fn component_name(designer: Jep106, part: u16) -> Option<&'static str> {
+    (designer == jep106::ARM)
+        .then(|| lookup(COMPONENTS, part))
+        .flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::format;
+
+    use super::*;
+    use crate::registers::dp::Dpidr;
+    use crate::romtable::ComponentId;
+
+    #[test]
+    fn debug_ports() {
+        assert_eq!(dp_name(jep106::ARM, 0xba), Some("SW-DP"));
+        assert_eq!(dp_name(jep106::ARM, 0xbc), Some("SW-DP (Cortex-M0+)"));
+        assert_eq!(dp_name(jep106::NORDIC, 0xba), None);
+        assert_eq!(format!("{}", Dpidr::from(0x2ba0_1477)), "ARM SW-DP DPv1 r2");
+        assert_eq!(
+            format!("{}", Dpidr::from(0x0bc1_1477)),
+            "ARM SW-DP (Cortex-M0+) DPv1 r0 MINDP"
+        );
+        assert_eq!(
+            format!("{}", Dpidr::from(0x1001_2289)),
+            "Nordic Semiconductor DP 0x00 DPv2 r1 MINDP"
+        );
+    }
+
+    #[test]
+    fn access_ports() {
+        assert_eq!(ap_name(0x2477_0011), Some("AHB-AP (Cortex-M3/M4)"));
+        assert_eq!(ap_name(0x0477_0031), Some("AHB-AP (Cortex-M0+)"));
+        assert_eq!(ap_name(0x0288_0000), None);
+    }
+
+    #[test]
+    fn cortex_m_components() {
+        let parts = [
+            (0x000, "Cortex-M3 SCS"),
+            (0x008, "Cortex-M0 SCS"),
+            (0x00c, "Cortex-M4 SCS"),
+            (0x00e, "Cortex-M7 FPB"),
+            (0x4c0, "Cortex-M0+ ROM"),
+            (0xd21, "Cortex-M33"),
+        ];
+        for (part, name) in parts {
+            assert_eq!(component_name(jep106::ARM, part), Some(name));
+        }
+        assert_eq!(component_name(jep106::ARM, 0xfff), None);
+        assert_eq!(component_name(jep106::ST, 0x00c), None);
+
+        let scs = ComponentId {
+            cidr: 0xb105_e00d,
+            pidr: 0x4_000b_b00c,
+        };
+        assert_eq!(format!("{}", scs), "ARM Cortex-M4 SCS r0 (GenericIp)");
+        let unknown = ComponentId {
+            cidr: 0xb105_900d,
+            pidr: 0x4_002b_bfff,
+        };
+        assert_eq!(format!("{}", unknown), "ARM part 0xfff r2 (CoreSight)");
+        let legacy = ComponentId {
+            cidr: 0xb105_100d,
+            pidr: 0x0000_0123,
+        };
+        assert_eq!(format!("{}", legacy), "legacy part 0x123 r0 (RomTable)");
+    }
+}
@@ -33,7 +33,7 @@ impl WriteRegister for BD2 {}
 make_register!(BD3, { (data, 0, 32) });
 
 impl APRegister for BD3 {
-    const ADDRESS: u8 = 0x18;
+    const ADDRESS: u8 = 0x1c;
 }
 
 impl ReadRegister for BD3 {}
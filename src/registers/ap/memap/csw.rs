@@ -3,6 +3,81 @@ use crate::{
     registers::ap::{APRegister, ReadRegister, WriteRegister},
 };
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Size {
+    Byte,
+    HalfWord,
+    Word,
+    DoubleWord,
+    Unknown(u8),
+}
+
+impl Size {
+    pub fn bytes(&self) -> u32 {
+        match self {
+            Size::Byte => 1,
+            Size::HalfWord => 2,
+            Size::Word => 4,
+            Size::DoubleWord => 8,
+            Size::Unknown(x) => 1 << x,
+        }
+    }
+}
+
+impl From<u32> for Size {
+    fn from(value: u32) -> Self {
+        match value {
+            0b000 => Size::Byte,
+            0b001 => Size::HalfWord,
+            0b010 => Size::Word,
+            0b011 => Size::DoubleWord,
+            x => Size::Unknown(x as u8),
+        }
+    }
+}
+
+impl From<Size> for u32 {
+    fn from(value: Size) -> Self {
+        match value {
+            Size::Byte => 0b000,
+            Size::HalfWord => 0b001,
+            Size::Word => 0b010,
+            Size::DoubleWord => 0b011,
+            Size::Unknown(x) => x as u32,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AddrInc {
+    Off,
+    Single,
+    Packed,
+    Unknown(u8),
+}
+
+impl From<u32> for AddrInc {
+    fn from(value: u32) -> Self {
+        match value {
+            0b00 => AddrInc::Off,
+            0b01 => AddrInc::Single,
+            0b10 => AddrInc::Packed,
+            x => AddrInc::Unknown(x as u8),
+        }
+    }
+}
+
+impl From<AddrInc> for u32 {
+    fn from(value: AddrInc) -> Self {
+        match value {
+            AddrInc::Off => 0b00,
+            AddrInc::Single => 0b01,
+            AddrInc::Packed => 0b10,
+            AddrInc::Unknown(x) => x as u32,
+        }
+    }
+}
+
 make_register!(CSW, {
     (dbgswenable, 31, 1, bool),
     (prot, 24, 7, u8),
@@ -12,8 +87,8 @@ make_register!(CSW, {
     (mode, 8, 4),
     (trinprog, 7, 1, bool),
     (deviceen, 6, 1, bool),
-    (addrinc, 4, 2),
-    (size, 0, 3)
+    (addrinc, 4, 2, AddrInc),
+    (size, 0, 3, Size)
 });
 
 impl APRegister for CSW {
@@ -5,7 +5,7 @@ pub mod banked;
 pub use banked::{BD0, BD1, BD2, BD3};
 
 pub mod csw;
-pub use csw::CSW;
+pub use csw::{AddrInc, Size, CSW};
 
 pub mod tar;
 pub use tar::Tar;
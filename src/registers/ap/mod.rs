@@ -1,7 +1,14 @@
+use core::fmt;
+
+use crate::jep106::Jep106;
 use crate::make_register;
+use crate::parts;
 
 pub trait APRegister {
     const ADDRESS: u8;
+    /// Offset in the 4 KiB register window of an ADIv6 AP, where the ADIv5
+    /// registers moved to 0xd00.
+    const ADIV6_ADDRESS: u16 = 0xd00 | Self::ADDRESS as u16;
 }
 
 pub trait ReadRegister: APRegister + From<u32> + core::fmt::Debug {}
@@ -72,7 +79,7 @@ make_register!(Idr, {
     (variant, 4, 4),
     (class, 13, 4, APClass),
     (revision, 28, 4),
-    (designer, 17, 11)
+    (designer, 17, 11, Jep106)
 });
 
 impl APRegister for Idr {
@@ -91,4 +98,21 @@ impl Idr {
     }
 }
 
+impl fmt::Display for Idr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ", self.designer())?;
+        match parts::ap_name(self.0) {
+            Some(name) => f.write_str(name)?,
+            None => write!(
+                f,
+                "{:?} {:?} variant {}",
+                self.class(),
+                self.ap_type(),
+                self.variant()
+            )?,
+        }
+        write!(f, " r{}", self.revision())
+    }
+}
+
 pub mod memap;
@@ -0,0 +1,23 @@
+use crate::make_register;
+
+use super::{MemoryRegister, ReadRegister, WriteRegister};
+
+/// Key that has to be in AIRCR.VECTKEY for a write to take effect.
+pub const VECTKEY: u32 = 0x05fa;
+
+make_register!(Aircr, {
+    (vectreset, 0, 1, bool),
+    (vectclractive, 1, 1, bool),
+    (sysresetreq, 2, 1, bool),
+    (prigroup, 8, 3, u8),
+    (endianness, 15, 1, bool),
+    (vectkey, 16, 16)
+});
+
+impl MemoryRegister for Aircr {
+    const ADDRESS: u32 = 0xe000_ed0c;
+}
+
+impl ReadRegister for Aircr {}
+
+impl WriteRegister for Aircr {}
@@ -0,0 +1,13 @@
+use crate::make_register;
+
+use super::{MemoryRegister, ReadRegister, WriteRegister};
+
+make_register!(Dcrdr, { (data, 0, 32) });
+
+impl MemoryRegister for Dcrdr {
+    const ADDRESS: u32 = 0xe000_edf8;
+}
+
+impl ReadRegister for Dcrdr {}
+
+impl WriteRegister for Dcrdr {}
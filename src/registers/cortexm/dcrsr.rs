@@ -0,0 +1,14 @@
+use crate::make_register;
+
+use super::{MemoryRegister, WriteRegister};
+
+make_register!(Dcrsr, {
+    (regsel, 0, 7, u8),
+    (regwnr, 16, 1, bool)
+});
+
+impl MemoryRegister for Dcrsr {
+    const ADDRESS: u32 = 0xe000_edf4;
+}
+
+impl WriteRegister for Dcrsr {}
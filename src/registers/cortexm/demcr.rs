@@ -0,0 +1,27 @@
+use crate::make_register;
+
+use super::{MemoryRegister, ReadRegister, WriteRegister};
+
+make_register!(Demcr, {
+    (vc_corereset, 0, 1, bool),
+    (vc_mmerr, 4, 1, bool),
+    (vc_nocperr, 5, 1, bool),
+    (vc_chkerr, 6, 1, bool),
+    (vc_staterr, 7, 1, bool),
+    (vc_buserr, 8, 1, bool),
+    (vc_interr, 9, 1, bool),
+    (vc_harderr, 10, 1, bool),
+    (mon_en, 16, 1, bool),
+    (mon_pend, 17, 1, bool),
+    (mon_step, 18, 1, bool),
+    (mon_req, 19, 1, bool),
+    (trcena, 24, 1, bool)
+});
+
+impl MemoryRegister for Demcr {
+    const ADDRESS: u32 = 0xe000_edfc;
+}
+
+impl ReadRegister for Demcr {}
+
+impl WriteRegister for Demcr {}
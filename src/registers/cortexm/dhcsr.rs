@@ -0,0 +1,29 @@
+use crate::make_register;
+
+use super::{MemoryRegister, ReadRegister, WriteRegister};
+
+/// Key that has to be in DHCSR.DBGKEY for a write to take effect.
+pub const DBGKEY: u32 = 0xa05f;
+
+make_register!(Dhcsr, {
+    (c_debugen, 0, 1, bool),
+    (c_halt, 1, 1, bool),
+    (c_step, 2, 1, bool),
+    (c_maskints, 3, 1, bool),
+    (c_snapstall, 5, 1, bool),
+    (s_regrdy, 16, 1, bool),
+    (s_halt, 17, 1, bool),
+    (s_sleep, 18, 1, bool),
+    (s_lockup, 19, 1, bool),
+    (s_retire_st, 24, 1, bool),
+    (s_reset_st, 25, 1, bool),
+    (dbgkey, 16, 16)
+});
+
+impl MemoryRegister for Dhcsr {
+    const ADDRESS: u32 = 0xe000_edf0;
+}
+
+impl ReadRegister for Dhcsr {}
+
+impl WriteRegister for Dhcsr {}
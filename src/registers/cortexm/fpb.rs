@@ -0,0 +1,39 @@
+use crate::make_register;
+
+use super::{MemoryRegister, ReadRegister, WriteRegister};
+
+/// Address of FP_COMP0, comparator `n` is at `FP_COMP0 + 4 * n`.
+pub const FP_COMP0: u32 = 0xe000_2008;
+
+make_register!(FpCtrl, {
+    (enable, 0, 1, bool),
+    (key, 1, 1, bool),
+    (num_code_lo, 4, 4, u8),
+    (num_lit, 8, 4, u8),
+    (num_code_hi, 12, 3, u8),
+    (rev, 28, 4, u8)
+});
+
+impl MemoryRegister for FpCtrl {
+    const ADDRESS: u32 = 0xe000_2000;
+}
+
+impl ReadRegister for FpCtrl {}
+
+impl WriteRegister for FpCtrl {}
+
+impl FpCtrl {
+    /// Number of instruction address comparators.
+    pub fn num_code(&self) -> u8 {
+        (self.num_code_hi() << 4) | self.num_code_lo()
+    }
+}
+
+// FPB version 1 compares COMP against bits 28:2 and REPLACE selects the
+// halfwords, version 2 compares BPADDR against bits 31:1.
+make_register!(FpComp, {
+    (enable, 0, 1, bool),
+    (comp, 2, 27),
+    (replace, 30, 2, u8),
+    (bpaddr, 1, 31)
+});
@@ -0,0 +1,27 @@
+//! Memory mapped debug registers of Cortex-M cores.
+
+pub trait MemoryRegister {
+    const ADDRESS: u32;
+}
+
+pub trait ReadRegister: MemoryRegister + From<u32> + core::fmt::Debug {}
+
+pub trait WriteRegister: MemoryRegister + Into<u32> + core::fmt::Debug {}
+
+pub mod dhcsr;
+pub use dhcsr::Dhcsr;
+
+pub mod dcrsr;
+pub use dcrsr::Dcrsr;
+
+pub mod dcrdr;
+pub use dcrdr::Dcrdr;
+
+pub mod demcr;
+pub use demcr::Demcr;
+
+pub mod aircr;
+pub use aircr::Aircr;
+
+pub mod fpb;
+pub use fpb::{FpComp, FpCtrl};
@@ -0,0 +1,17 @@
+use crate::make_register;
+
+use super::{DPRegister, WriteRegister};
+
+make_register!(Abort, {
+    (orunerrclr, 4, 1, bool),
+    (wderrclr, 3, 1, bool),
+    (stkerrclr, 2, 1, bool),
+    (stkcmpclr, 1, 1, bool),
+    (dapabort, 0, 1, bool)
+});
+
+impl DPRegister for Abort {
+    const A: [bool; 2] = [false, false];
+}
+
+impl WriteRegister for Abort {}
@@ -0,0 +1,24 @@
+use crate::make_register;
+
+use super::{DPRegister, ReadRegister};
+
+make_register!(BasePtr0, {
+    (ptr, 12, 20),
+    (valid, 0, 1, bool)
+});
+
+impl DPRegister for BasePtr0 {
+    const A: [bool; 2] = [false, false];
+    const BANK: Option<u8> = Some(2);
+}
+
+impl ReadRegister for BasePtr0 {}
+
+make_register!(BasePtr1, { (ptr, 0, 32) });
+
+impl DPRegister for BasePtr1 {
+    const A: [bool; 2] = [false, false];
+    const BANK: Option<u8> = Some(3);
+}
+
+impl ReadRegister for BasePtr1 {}
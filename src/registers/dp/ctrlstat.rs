@@ -22,6 +22,7 @@ make_register!(CtrlStat, {
 
 impl DPRegister for CtrlStat {
     const A: [bool; 2] = [true, false];
+    const BANK: Option<u8> = Some(0);
 }
 
 impl ReadRegister for CtrlStat {}
@@ -0,0 +1,17 @@
+use crate::make_register;
+
+use super::{DPRegister, ReadRegister, WriteRegister};
+
+make_register!(Dlcr, {
+    (turnround, 8, 2, u8),
+    (wiremode, 6, 2, u8)
+});
+
+impl DPRegister for Dlcr {
+    const A: [bool; 2] = [true, false];
+    const BANK: Option<u8> = Some(1);
+}
+
+impl ReadRegister for Dlcr {}
+
+impl WriteRegister for Dlcr {}
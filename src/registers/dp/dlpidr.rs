@@ -0,0 +1,15 @@
+use crate::make_register;
+
+use super::{DPRegister, ReadRegister};
+
+make_register!(Dlpidr, {
+    (tinstance, 28, 4, u8),
+    (protvsn, 0, 4, u8)
+});
+
+impl DPRegister for Dlpidr {
+    const A: [bool; 2] = [true, false];
+    const BANK: Option<u8> = Some(3);
+}
+
+impl ReadRegister for Dlpidr {}
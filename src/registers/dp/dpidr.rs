@@ -0,0 +1,38 @@
+use core::fmt;
+
+use crate::jep106::Jep106;
+use crate::make_register;
+use crate::parts;
+
+use super::{DPRegister, ReadRegister};
+
+make_register!(Dpidr, {
+    (revision, 28, 4, u8),
+    (partno, 20, 8, u8),
+    (mindp, 16, 1, bool),
+    (version, 12, 4, u8),
+    (designer, 1, 11, Jep106),
+    (present, 0, 1, bool)
+});
+
+impl DPRegister for Dpidr {
+    const A: [bool; 2] = [false, false];
+    const BANK: Option<u8> = Some(0);
+}
+
+impl ReadRegister for Dpidr {}
+
+impl fmt::Display for Dpidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ", self.designer())?;
+        match parts::dp_name(self.designer(), self.partno()) {
+            Some(name) => f.write_str(name)?,
+            None => write!(f, "DP {:#04x}", self.partno())?,
+        }
+        write!(f, " DPv{} r{}", self.version(), self.revision())?;
+        if self.mindp() {
+            f.write_str(" MINDP")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,12 @@
+use crate::make_register;
+
+use super::{DPRegister, ReadRegister};
+
+make_register!(EventStat, { (ea, 0, 1, bool) });
+
+impl DPRegister for EventStat {
+    const A: [bool; 2] = [true, false];
+    const BANK: Option<u8> = Some(4);
+}
+
+impl ReadRegister for EventStat {}
@@ -1,3 +1,6 @@
+use core::fmt;
+
+use crate::jep106::Jep106;
 use crate::make_register;
 
 use super::{DPRegister, ReadRegister};
@@ -5,12 +8,25 @@ use super::{DPRegister, ReadRegister};
 make_register!(Idcode, {
     (version, 28, 4),
     (partno, 12, 16),
-    (designer, 1, 11),
+    (designer, 1, 11, Jep106),
     (present, 0, 1, bool)
 });
 
 impl DPRegister for Idcode {
     const A: [bool; 2] = [false, false];
+    const BANK: Option<u8> = Some(0);
 }
 
 impl ReadRegister for Idcode {}
+
+impl fmt::Display for Idcode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} part {:#06x} version {}",
+            self.designer(),
+            self.partno(),
+            self.version()
+        )
+    }
+}
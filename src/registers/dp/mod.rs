@@ -1,5 +1,9 @@
 pub trait DPRegister {
     const A: [bool; 2];
+    /// DPBANKSEL value for registers banked at address 0x4, and for DPIDR and
+    /// BASEPTR0/1 at 0x0 of an ADIv6 DP. Other banks are only selected for the
+    /// access, bank 0 is restored even if it fails.
+    const BANK: Option<u8> = None;
 }
 
 pub trait ReadRegister: DPRegister + From<u32> + core::fmt::Debug {}
@@ -17,3 +21,30 @@ pub use select::Select;
 
 pub mod rdbuff;
 pub use rdbuff::RdBuff;
+
+pub mod abort;
+pub use abort::Abort;
+
+pub mod targetsel;
+pub use targetsel::TargetSel;
+
+pub mod dpidr;
+pub use dpidr::Dpidr;
+
+pub mod dlcr;
+pub use dlcr::Dlcr;
+
+pub mod targetid;
+pub use targetid::TargetId;
+
+pub mod dlpidr;
+pub use dlpidr::Dlpidr;
+
+pub mod eventstat;
+pub use eventstat::EventStat;
+
+pub mod select1;
+pub use select1::Select1;
+
+pub mod baseptr;
+pub use baseptr::{BasePtr0, BasePtr1};
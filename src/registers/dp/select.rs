@@ -3,9 +3,10 @@ use crate::make_register;
 use super::{DPRegister, ReadRegister, WriteRegister};
 
 make_register!(Select, {
-    (ctrlsel, 0, 1),
+    (dpbanksel, 0, 4, u8),
     (apbanksel, 4, 4, u8),
-    (apsel, 24, 8, u8)
+    (apsel, 24, 8, u8),
+    (addr, 4, 28)
 });
 
 impl DPRegister for Select {
@@ -0,0 +1,14 @@
+use crate::make_register;
+
+use super::{DPRegister, ReadRegister, WriteRegister};
+
+make_register!(Select1, { (addr, 0, 32) });
+
+impl DPRegister for Select1 {
+    const A: [bool; 2] = [true, false];
+    const BANK: Option<u8> = Some(5);
+}
+
+impl ReadRegister for Select1 {}
+
+impl WriteRegister for Select1 {}
@@ -0,0 +1,32 @@
+use core::fmt;
+
+use crate::jep106::Jep106;
+use crate::make_register;
+
+use super::{DPRegister, ReadRegister};
+
+make_register!(TargetId, {
+    (trevision, 28, 4, u8),
+    (tpartno, 12, 16),
+    (tdesigner, 1, 11, Jep106),
+    (present, 0, 1, bool)
+});
+
+impl DPRegister for TargetId {
+    const A: [bool; 2] = [true, false];
+    const BANK: Option<u8> = Some(2);
+}
+
+impl ReadRegister for TargetId {}
+
+impl fmt::Display for TargetId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} part {:#06x} r{}",
+            self.tdesigner(),
+            self.tpartno(),
+            self.trevision()
+        )
+    }
+}
@@ -0,0 +1,16 @@
+use crate::jep106::Jep106;
+use crate::make_register;
+
+use super::{DPRegister, WriteRegister};
+
+make_register!(TargetSel, {
+    (tinstance, 28, 4, u8),
+    (tpartno, 12, 16),
+    (tdesigner, 1, 11, Jep106)
+});
+
+impl DPRegister for TargetSel {
+    const A: [bool; 2] = [true, true];
+}
+
+impl WriteRegister for TargetSel {}
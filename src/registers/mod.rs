@@ -1,4 +1,5 @@
 pub mod ap;
+pub mod cortexm;
 pub mod dp;
 
 macro_rules! make_register {
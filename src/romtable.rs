@@ -0,0 +1,457 @@
+//! CoreSight ROM table walker.
+//!
+//! Every CoreSight component has a 4 KiB block ending in its peripheral and
+//! component ID registers. ROM tables list the base addresses of further
+//! components, relative to the table itself.
+
+use core::fmt;
+
+use heapless::Vec;
+use log::info;
+
+use crate::io::SwdIo;
+use crate::jep106::{self, Jep106};
+use crate::memap::MemAp;
+use crate::parts;
+use crate::swd::RequestError;
+
+/// Most components reported by `MemAp::walk_rom_table`.
+pub const MAX_COMPONENTS: usize = 64;
+/// Deepest nesting of ROM tables followed.
+pub const MAX_DEPTH: usize = 8;
+
+/// Entries of a class 0x1 ROM table, 0x000 to 0xefc.
+const MAX_ENTRIES: u32 = 960;
+/// Entries of a class 0x9 CoreSight ROM table, 0x000 to 0x7fc, which has
+/// power domain and reset registers above them.
+const MAX_CORESIGHT_ENTRIES: u32 = 512;
+const ENTRY_CHUNK: usize = 16;
+
+/// Offset of PIDR4, the first of the 12 ID registers.
+const ID_OFFSET: u32 = 0xfd0;
+const DEVARCH_OFFSET: u32 = 0xfbc;
+const DEVTYPE_OFFSET: u32 = 0xfcc;
+
+/// CIDR with the class field cleared.
+const CIDR_PREAMBLE: u32 = 0xb105_000d;
+const CIDR_CLASS_MASK: u32 = 0x0000_f000;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ComponentClass {
+    GenericVerification,
+    RomTable,
+    CoreSight,
+    PeripheralTest,
+    GenericIp,
+    PrimeCell,
+    Unknown(u8),
+}
+
+impl From<u32> for ComponentClass {
+    fn from(value: u32) -> Self {
+        match value {
+            0x0 => ComponentClass::GenericVerification,
+            0x1 => ComponentClass::RomTable,
+            0x9 => ComponentClass::CoreSight,
+            0xb => ComponentClass::PeripheralTest,
+            0xe => ComponentClass::GenericIp,
+            0xf => ComponentClass::PrimeCell,
+            x => ComponentClass::Unknown(x as u8),
+        }
+    }
+}
+
+/// CIDR0-3 and PIDR0-7 of a component, each register holding one byte.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ComponentId {
+    pub cidr: u32,
+    pub pidr: u64,
+}
+
+impl ComponentId {
+    /// Assemble the IDs from the 12 registers starting at PIDR4.
+    fn from_registers(regs: &[u32; 12]) -> Self {
+        let byte = |i: usize| u64::from(regs[i] & 0xff);
+        let pidr = (0..4).fold(0, |pidr, i| pidr | (byte(i) << (32 + 8 * i)))
+            | (0..4).fold(0, |pidr, i| pidr | (byte(4 + i) << (8 * i)));
+        let cidr = (0..4).fold(0, |cidr, i| cidr | ((byte(8 + i) as u32) << (8 * i)));
+        Self { cidr, pidr }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.cidr & !CIDR_CLASS_MASK == CIDR_PREAMBLE
+    }
+
+    pub fn class(&self) -> ComponentClass {
+        ((self.cidr & CIDR_CLASS_MASK) >> 12).into()
+    }
+
+    pub fn part(&self) -> u16 {
+        (self.pidr & 0xfff) as u16
+    }
+
+    pub fn revision(&self) -> u8 {
+        ((self.pidr >> 20) & 0xf) as u8
+    }
+
+    /// JEP106 designer, or `None` for legacy parts without one.
+    pub fn designer(&self) -> Option<Jep106> {
+        let used = self.pidr & (1 << 19) != 0;
+        let identity = ((self.pidr >> 12) & 0x7f) as u8;
+        let continuation = ((self.pidr >> 32) & 0xf) as u8;
+        used.then_some(Jep106::new(continuation, identity))
+    }
+
+    /// Name of the part, if it is a known one.
+    pub fn name(&self) -> Option<&'static str> {
+        parts::component_name(self.designer()?, self.part())
+    }
+
+    /// Number of 4 KiB blocks, the ID registers are in the last one.
+    pub fn blocks(&self) -> u32 {
+        1 << ((self.pidr >> 36) & 0xf)
+    }
+}
+
+impl fmt::Display for ComponentId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.designer() {
+            Some(designer) => write!(f, "{} ", designer)?,
+            None => f.write_str("legacy ")?,
+        }
+        match self.name() {
+            Some(name) => f.write_str(name)?,
+            None => write!(f, "part {:#05x}", self.part())?,
+        }
+        write!(f, " r{} ({:?})", self.revision(), self.class())
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ComponentKind {
+    RomTable,
+    /// System Control Space of a Cortex-M core.
+    Scs,
+    Dwt,
+    Fpb,
+    Itm,
+    Tpiu,
+    Etm,
+    Cti,
+    Other,
+}
+
+/// A component found by `MemAp::walk_rom_table`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Component {
+    /// Address of the 4 KiB block holding the ID registers.
+    pub base: u32,
+    pub id: ComponentId,
+    /// DEVARCH and DEVTYPE, read for CoreSight class components only.
+    pub devarch: u32,
+    pub devtype: u32,
+    pub kind: ComponentKind,
+    /// Index of the ROM table listing this component, `None` for the root.
+    pub parent: Option<u8>,
+    pub depth: u8,
+}
+
+impl Component {
+    pub fn designer_name(&self) -> Option<&'static str> {
+        self.id.designer().and_then(|designer| designer.name())
+    }
+
+    fn identify(id: ComponentId, devarch: u32, devtype: u32) -> ComponentKind {
+        // DEVARCH.PRESENT with ARM as architect.
+        let archid = (devarch & (1 << 20) != 0 && devarch >> 21 == u32::from(jep106::ARM))
+            .then_some(devarch & 0xffff);
+        match (id.class(), archid) {
+            (ComponentClass::RomTable, _) => ComponentKind::RomTable,
+            (ComponentClass::CoreSight, Some(0x0af7)) => ComponentKind::RomTable,
+            (ComponentClass::CoreSight, Some(0x1a01)) => ComponentKind::Itm,
+            (ComponentClass::CoreSight, Some(0x1a02)) => ComponentKind::Dwt,
+            (ComponentClass::CoreSight, Some(0x1a03)) => ComponentKind::Fpb,
+            (ComponentClass::CoreSight, Some(0x2a04)) => ComponentKind::Scs,
+            (ComponentClass::CoreSight, Some(0x1a14)) => ComponentKind::Cti,
+            (ComponentClass::CoreSight, Some(archid)) if archid & 0x0fff == 0x0a13 => {
+                ComponentKind::Etm
+            }
+            // Major and sub type: trace port, processor trace, trigger matrix.
+            (ComponentClass::CoreSight, _) => match devtype & 0xff {
+                0x11 => ComponentKind::Tpiu,
+                0x13 => ComponentKind::Etm,
+                0x14 => ComponentKind::Cti,
+                _ => ComponentKind::Other,
+            },
+            // ARMv6-M and ARMv7-M cores predate DEVARCH.
+            (ComponentClass::GenericIp, _) if id.designer() == Some(jep106::ARM) => {
+                match id.part() {
+                    0x000 | 0x008 | 0x00c => ComponentKind::Scs,
+                    0x001 => ComponentKind::Itm,
+                    0x002 | 0x00a => ComponentKind::Dwt,
+                    0x003 | 0x00b | 0x00e => ComponentKind::Fpb,
+                    _ => ComponentKind::Other,
+                }
+            }
+            _ => ComponentKind::Other,
+        }
+    }
+}
+
+/// A ROM table on the walk stack and the next entry to read from it.
+struct Table {
+    base: u32,
+    index: u8,
+    entry: u32,
+    max_entries: u32,
+    entries: [u32; ENTRY_CHUNK],
+}
+
+impl Table {
+    fn new(component: &Component, index: u8) -> Self {
+        let max_entries = match component.id.class() {
+            ComponentClass::CoreSight => MAX_CORESIGHT_ENTRIES,
+            _ => MAX_ENTRIES,
+        };
+        Self {
+            base: component.base,
+            index,
+            entry: 0,
+            max_entries,
+            entries: [0; ENTRY_CHUNK],
+        }
+    }
+}
+
+impl<Io: SwdIo> MemAp<'_, Io> {
+    /// Read the ID registers of the component whose ID block is at `base`.
+    pub async fn component_id(&mut self, base: u32) -> Result<ComponentId, RequestError> {
+        let mut regs = [0; 12];
+        self.read_block(base + ID_OFFSET, &mut regs).await?;
+        Ok(ComponentId::from_registers(&regs))
+    }
+
+    /// Walk the ROM table in BASE and everything it references.
+    pub async fn walk_base_rom_table(
+        &mut self,
+    ) -> Result<Vec<Component, MAX_COMPONENTS>, RequestError> {
+        let base = self.base().await?;
+        if !base.present() {
+            return Ok(Vec::new());
+        }
+        self.walk_rom_table(base.address()).await
+    }
+
+    /// Walk the ROM table at `base` depth first.
+    ///
+    /// Components are listed in the order they are found, each ROM table
+    /// before its entries, so the tree can be rebuilt from `parent`.
+    /// Components that cannot be read are skipped.
+    pub async fn walk_rom_table(
+        &mut self,
+        base: u32,
+    ) -> Result<Vec<Component, MAX_COMPONENTS>, RequestError> {
+        let mut components = Vec::new();
+        let mut tables: Vec<Table, MAX_DEPTH> = Vec::new();
+        if let Some(root) = self.visit(base, None, 0).await? {
+            if components.push(root).is_ok() && root.kind == ComponentKind::RomTable {
+                let _ = tables.push(Table::new(&root, 0));
+            }
+        }
+
+        while let Some(table) = tables.last_mut() {
+            let chunk = table.entry as usize % ENTRY_CHUNK;
+            if chunk == 0 {
+                if table.entry >= table.max_entries {
+                    tables.pop();
+                    continue;
+                }
+                let address = table.base + table.entry * 4;
+                self.read_block(address, &mut table.entries).await?;
+            }
+            let entry = table.entries[chunk];
+            table.entry += 1;
+            let (address, parent) = (table.base.wrapping_add(entry & 0xffff_f000), table.index);
+            match entry {
+                0 => {
+                    tables.pop();
+                    continue;
+                }
+                // Not present, or an ADIv6 power domain entry.
+                entry if entry & 0x1 == 0 => continue,
+                _ if components.iter().any(|c: &Component| c.base == address) => continue,
+                _ => {}
+            }
+            let depth = tables.len() as u8;
+            let Some(component) = self.visit(address, Some(parent), depth).await? else {
+                continue;
+            };
+            let index = components.len() as u8;
+            if components.push(component).is_err() {
+                break;
+            }
+            if component.kind == ComponentKind::RomTable {
+                // Tables nested deeper than MAX_DEPTH are listed but not walked.
+                let _ = tables.push(Table::new(&component, index));
+            }
+        }
+        Ok(components)
+    }
+
+    /// Identify the component at `base`, `None` if it is unreadable or has
+    /// no valid component ID.
+    async fn visit(
+        &mut self,
+        base: u32,
+        parent: Option<u8>,
+        depth: u8,
+    ) -> Result<Option<Component>, RequestError> {
+        let id = match self.component_id(base).await {
+            Ok(id) => id,
+            Err(RequestError::Sticky(_)) => {
+                info!("Component at {:#010x} is not readable", base);
+                return Ok(None);
+            }
+            Err(err) => return Err(err),
+        };
+        if !id.is_valid() {
+            info!("No component at {:#010x}: {:x?}", base, id);
+            return Ok(None);
+        }
+        let (devarch, devtype) = match id.class() {
+            ComponentClass::CoreSight => (
+                self.read_32(base + DEVARCH_OFFSET).await?,
+                self.read_32(base + DEVTYPE_OFFSET).await?,
+            ),
+            _ => (0, 0),
+        };
+        let kind = Component::identify(id, devarch, devtype);
+        info!("{:#010x}: {:?} {}", base, kind, id);
+        Ok(Some(Component {
+            base,
+            id,
+            devarch,
+            devtype,
+            kind,
+            parent,
+            depth,
+        }))
+    }
+}
+
+#[cfg(all(test, feature = "sim"))]
+mod tests {
+    use super::*;
+    use crate::sim::{block_on, MemApSim, Memory, SimIo, Target, IDCODE};
+    use crate::swd::Swd;
+
+    const ROOT: u32 = 0xe00f_f000;
+    const SCS: u32 = 0xe000_e000;
+    const CORESIGHT_TABLE: u32 = 0xe004_0000;
+    const CTI: u32 = 0xe004_1000;
+    const ITM: u32 = 0xe004_2000;
+
+    /// PIDR of an ARM part.
+    fn arm_pidr(part: u16) -> u64 {
+        (4 << 32) | (1 << 19) | (0x3b << 12) | u64::from(part)
+    }
+
+    fn add_component(memory: &mut Memory, base: u32, class: u32, pidr: u64, devarch: u32) {
+        let cidr = CIDR_PREAMBLE | (class << 12);
+        for i in 0..4 {
+            let id = |value: u64, byte: u32| (value >> (8 * byte)) as u32 & 0xff;
+            memory
+                .write_word(base + ID_OFFSET + 4 * i, id(pidr, 4 + i))
+                .unwrap();
+            memory
+                .write_word(base + ID_OFFSET + 0x10 + 4 * i, id(pidr, i))
+                .unwrap();
+            memory
+                .write_word(base + ID_OFFSET + 0x20 + 4 * i, id(cidr.into(), i))
+                .unwrap();
+        }
+        memory.write_word(base + DEVARCH_OFFSET, devarch).unwrap();
+    }
+
+    #[test]
+    fn nested_rom_tables() {
+        let mut memory = Memory::new();
+        add_component(&mut memory, ROOT, 0x1, arm_pidr(0x4c4), 0);
+        memory
+            .write_word(ROOT, SCS.wrapping_sub(ROOT) | 0x3)
+            .unwrap();
+        memory
+            .write_word(ROOT + 4, CORESIGHT_TABLE.wrapping_sub(ROOT) | 0x3)
+            .unwrap();
+        add_component(&mut memory, SCS, 0xe, arm_pidr(0x00c), 0);
+        add_component(
+            &mut memory,
+            CORESIGHT_TABLE,
+            0x9,
+            arm_pidr(0x4c7),
+            0x4770_0af7,
+        );
+        memory
+            .write_word(CORESIGHT_TABLE, (CTI - CORESIGHT_TABLE) | 0x3)
+            .unwrap();
+        // Not present entries up to the last one, followed by a register
+        // that looks like an entry but is past the end of the table.
+        for entry in 1..MAX_CORESIGHT_ENTRIES {
+            memory.write_word(CORESIGHT_TABLE + 4 * entry, 0x2).unwrap();
+        }
+        memory
+            .write_word(CORESIGHT_TABLE + 0x800, (ITM - CORESIGHT_TABLE) | 0x3)
+            .unwrap();
+        add_component(&mut memory, CTI, 0x9, arm_pidr(0x9a6), 0x4770_1a14);
+        add_component(&mut memory, ITM, 0x9, arm_pidr(0x9a9), 0x4770_1a01);
+
+        let target = Target::new(IDCODE).with_ap(MemApSim::new(ROOT | 0x3, memory));
+        let mut swd = Swd::new(SimIo::new(target));
+        let components = block_on(async {
+            swd.reset().await.unwrap();
+            swd.memap(0).walk_base_rom_table().await.unwrap()
+        });
+        let found: std::vec::Vec<_> = components
+            .iter()
+            .map(|c| (c.base, c.kind, c.id.class(), c.id.pidr, c.parent, c.depth))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (
+                    ROOT,
+                    ComponentKind::RomTable,
+                    ComponentClass::RomTable,
+                    arm_pidr(0x4c4),
+                    None,
+                    0
+                ),
+                (
+                    SCS,
+                    ComponentKind::Scs,
+                    ComponentClass::GenericIp,
+                    arm_pidr(0x00c),
+                    Some(0),
+                    1
+                ),
+                (
+                    CORESIGHT_TABLE,
+                    ComponentKind::RomTable,
+                    ComponentClass::CoreSight,
+                    arm_pidr(0x4c7),
+                    Some(0),
+                    1
+                ),
+                (
+                    CTI,
+                    ComponentKind::Cti,
+                    ComponentClass::CoreSight,
+                    arm_pidr(0x9a6),
+                    Some(2),
+                    2
+                ),
+            ]
+        );
+        assert_eq!(components[0].id.designer(), Some(jep106::ARM));
+    }
+}
@@ -0,0 +1,220 @@
+use crate::registers::cortexm::{
+    aircr::VECTKEY, dhcsr::DBGKEY, fpb::FP_COMP0, Aircr, Dcrdr, Dcrsr, Demcr, Dhcsr, FpCtrl,
+    MemoryRegister,
+};
+
+/// Debug registers of the core modelled by [`CoreSim`].
+pub const DEBUG_REGISTERS: core::ops::Range<u32> = 0xe000_edf0..0xe000_ee00;
+
+/// Instruction address comparators of the modelled FPB.
+pub const FPB_COMPARATORS: usize = 6;
+
+/// Software model of the halting debug registers and the reset control of a
+/// Cortex-M core.
+///
+/// The core does not execute anything, running just retires instructions and
+/// a single step moves PC past one 16-bit instruction.
+#[derive(Debug, Clone)]
+pub struct CoreSim {
+    pub debugen: bool,
+    pub maskints: bool,
+    pub halted: bool,
+    pub sleeping: bool,
+    pub lockup: bool,
+    /// Instructions executed by single steps.
+    pub steps: u32,
+    /// Core registers indexed by DCRSR.REGSEL.
+    pub registers: [u32; 0x60],
+    pub dcrdr: u32,
+    pub demcr: Demcr,
+    /// Resets requested through AIRCR.
+    pub resets: u32,
+    /// Loaded into PC on every reset, as if read from the vector table.
+    pub reset_vector: u32,
+    /// FP_CTRL.REV, 0 for FPB version 1 and 1 for version 2.
+    pub fpb_revision: u8,
+    pub fpb_enable: bool,
+    /// FP_COMP registers, one per comparator.
+    pub fp_comp: [u32; FPB_COMPARATORS],
+    system_reset: bool,
+    /// Held in reset by nRESET.
+    in_reset: bool,
+    retired: bool,
+    reset: bool,
+}
+
+impl Default for CoreSim {
+    fn default() -> Self {
+        Self {
+            debugen: false,
+            maskints: false,
+            halted: false,
+            sleeping: false,
+            lockup: false,
+            steps: 0,
+            registers: [0; 0x60],
+            dcrdr: 0,
+            demcr: Demcr::default(),
+            resets: 0,
+            reset_vector: 0,
+            fpb_revision: 0,
+            fpb_enable: false,
+            fp_comp: [0; FPB_COMPARATORS],
+            system_reset: false,
+            in_reset: false,
+            retired: false,
+            reset: false,
+        }
+    }
+}
+
+impl CoreSim {
+    /// A running core that has just come out of reset.
+    pub fn new() -> Self {
+        Self {
+            reset: true,
+            ..Default::default()
+        }
+    }
+
+    /// Whether a system reset was requested since the last call.
+    pub fn take_system_reset(&mut self) -> bool {
+        core::mem::take(&mut self.system_reset)
+    }
+
+    /// Whether `address` is one of the modelled registers.
+    pub fn maps(address: u32) -> bool {
+        DEBUG_REGISTERS.contains(&address)
+            || address == Aircr::ADDRESS
+            || Self::fp_comp_index(address).is_some()
+            || address == FpCtrl::ADDRESS
+    }
+
+    fn fp_comp_index(address: u32) -> Option<usize> {
+        let index = (address.checked_sub(FP_COMP0)? / 4) as usize;
+        (index < FPB_COMPARATORS).then_some(index)
+    }
+
+    pub fn read(&mut self, address: u32) -> u32 {
+        match address {
+            Dhcsr::ADDRESS => {
+                let dhcsr = Dhcsr::default()
+                    .set_c_debugen(self.debugen)
+                    .set_c_halt(self.halted)
+                    .set_c_maskints(self.maskints)
+                    .set_s_regrdy(true)
+                    .set_s_halt(self.halted)
+                    .set_s_sleep(self.sleeping && !self.halted)
+                    .set_s_lockup(self.lockup)
+                    .set_s_retire_st(self.retired)
+                    .set_s_reset_st(self.reset);
+                self.retired = false;
+                // S_RESET_ST stays set while the core is held in reset.
+                self.reset = self.in_reset;
+                dhcsr.into()
+            }
+            Dcrdr::ADDRESS => self.dcrdr,
+            Demcr::ADDRESS => self.demcr.into(),
+            // VECTKEYSTAT reads as the inverted key.
+            Aircr::ADDRESS => 0xfa05_0000,
+            FpCtrl::ADDRESS => FpCtrl::default()
+                .set_enable(self.fpb_enable)
+                .set_num_code_lo(FPB_COMPARATORS as u8)
+                .set_num_lit(2)
+                .set_rev(self.fpb_revision)
+                .into(),
+            address => match Self::fp_comp_index(address) {
+                Some(index) => self.fp_comp[index],
+                None => 0,
+            },
+        }
+    }
+
+    pub fn write(&mut self, address: u32, value: u32) {
+        match address {
+            Dhcsr::ADDRESS => self.write_dhcsr(value.into()),
+            Dcrsr::ADDRESS => self.write_dcrsr(value.into()),
+            Dcrdr::ADDRESS => self.dcrdr = value,
+            Demcr::ADDRESS => self.demcr = value.into(),
+            Aircr::ADDRESS => self.write_aircr(value.into()),
+            FpCtrl::ADDRESS => {
+                let ctrl = FpCtrl::from(value);
+                if ctrl.key() {
+                    self.fpb_enable = ctrl.enable();
+                }
+            }
+            address => {
+                if let Some(index) = Self::fp_comp_index(address) {
+                    self.fp_comp[index] = value;
+                }
+            }
+        }
+    }
+
+    /// Hold the core in reset while nRESET is low, it leaves reset when
+    /// released.
+    pub fn set_nreset(&mut self, asserted: bool) {
+        self.in_reset = asserted;
+        self.reset_core();
+    }
+
+    /// Reset the core on a debug reset request from the DP.
+    pub fn debug_reset(&mut self) {
+        self.reset_core();
+    }
+
+    fn write_aircr(&mut self, aircr: Aircr) {
+        if aircr.vectkey() == VECTKEY && (aircr.sysresetreq() || aircr.vectreset()) {
+            self.resets += 1;
+            self.system_reset |= aircr.sysresetreq();
+            self.reset_core();
+        }
+    }
+
+    fn reset_core(&mut self) {
+        self.reset = true;
+        self.sleeping = false;
+        self.lockup = false;
+        self.maskints = false;
+        self.registers[15] = self.reset_vector;
+        self.halted = !self.in_reset && self.debugen && self.demcr.vc_corereset();
+    }
+
+    fn write_dcrsr(&mut self, dcrsr: Dcrsr) {
+        let Some(register) = self.registers.get_mut(dcrsr.regsel() as usize) else {
+            return;
+        };
+        if !self.halted {
+            return;
+        }
+        match dcrsr.regwnr() {
+            true => *register = self.dcrdr,
+            false => self.dcrdr = *register,
+        }
+    }
+
+    fn write_dhcsr(&mut self, dhcsr: Dhcsr) {
+        if dhcsr.dbgkey() != DBGKEY {
+            return;
+        }
+        self.debugen = dhcsr.c_debugen();
+        if !self.debugen {
+            self.halted = false;
+            self.maskints = false;
+            return;
+        }
+        if self.halted {
+            self.maskints = dhcsr.c_maskints();
+        }
+        if dhcsr.c_halt() {
+            self.halted = true;
+        } else if dhcsr.c_step() && self.halted {
+            self.steps += 1;
+            self.registers[15] = self.registers[15].wrapping_add(2);
+            self.retired = true;
+        } else {
+            self.halted = false;
+            self.retired = true;
+        }
+    }
+}
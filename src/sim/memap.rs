@@ -0,0 +1,130 @@
+use crate::registers::ap::memap::{AddrInc, Size, CSW};
+
+use super::memory::{BusFault, Memory};
+
+/// Software model of a MEM-AP in front of a [`Memory`].
+pub struct MemApSim {
+    pub idr: u32,
+    pub base: u32,
+    pub cfg: u32,
+    pub csw: CSW,
+    pub tar: u32,
+    pub memory: Memory,
+    /// Byte and halfword accesses are implemented.
+    pub sub_word: bool,
+    /// Packed transfers are implemented.
+    pub packed: bool,
+}
+
+impl MemApSim {
+    /// An AHB-AP designed by ARM with byte, halfword and packed transfers.
+    pub fn new(base: u32, memory: Memory) -> Self {
+        Self {
+            idr: 0x24770011,
+            base,
+            cfg: 0,
+            csw: CSW::default().set_deviceen(true).set_size(Size::Word),
+            tar: 0,
+            memory,
+            sub_word: true,
+            packed: true,
+        }
+    }
+
+    pub fn read(&mut self, address: u8) -> Result<u32, BusFault> {
+        Ok(match address {
+            0x00 => self.csw.into(),
+            0x04 => self.tar,
+            0x0c => self.read_drw()?,
+            0x10..=0x1c => self
+                .memory
+                .read_word((self.tar & !0xf) | (address as u32 & 0xc))?,
+            0xf4 => self.cfg,
+            0xf8 => self.base,
+            0xfc => self.idr,
+            _ => 0,
+        })
+    }
+
+    pub fn write(&mut self, address: u8, value: u32) -> Result<(), BusFault> {
+        match address {
+            0x00 => self.write_csw(value.into()),
+            0x04 => self.tar = value,
+            0x0c => self.write_drw(value)?,
+            0x10..=0x1c => self
+                .memory
+                .write_word((self.tar & !0xf) | (address as u32 & 0xc), value)?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn write_csw(&mut self, csw: CSW) {
+        let size = match csw.size() {
+            Size::Word => Size::Word,
+            size @ (Size::Byte | Size::HalfWord) if self.sub_word => size,
+            _ => self.csw.size(),
+        };
+        let addrinc = match csw.addrinc() {
+            AddrInc::Packed if !self.packed => self.csw.addrinc(),
+            AddrInc::Unknown(_) => self.csw.addrinc(),
+            addrinc => addrinc,
+        };
+        self.csw = csw
+            .set_size(size)
+            .set_addrinc(addrinc)
+            .set_deviceen(true)
+            .set_trinprog(false);
+    }
+
+    fn transfer_bytes(&self) -> u32 {
+        self.csw.size().bytes()
+    }
+
+    /// Addresses touched by one DRW access.
+    fn transfers(&self) -> impl Iterator<Item = u32> {
+        let size = self.transfer_bytes();
+        let count = match self.csw.addrinc() {
+            AddrInc::Packed => 4 / size,
+            _ => 1,
+        };
+        let tar = self.tar;
+        (0..count).map(move |i| tar.wrapping_add(i * size))
+    }
+
+    fn increment(&mut self) {
+        let step = match self.csw.addrinc() {
+            AddrInc::Off => return,
+            AddrInc::Single => self.transfer_bytes(),
+            _ => 4,
+        };
+        // Auto-increment is only guaranteed inside a 1 KiB block.
+        self.tar = (self.tar & !0x3ff) | (self.tar.wrapping_add(step) & 0x3ff);
+    }
+
+    fn lane_mask(&self, address: u32) -> u32 {
+        let bits = self.transfer_bytes() * 8;
+        let mask = 0xffffffff >> (32 - bits);
+        mask << ((address & 0x3) * 8)
+    }
+
+    fn read_drw(&mut self) -> Result<u32, BusFault> {
+        let mut value = 0;
+        for address in self.transfers() {
+            let mask = self.lane_mask(address);
+            value |= self.memory.read_word(address)? & mask;
+        }
+        self.increment();
+        Ok(value)
+    }
+
+    fn write_drw(&mut self, value: u32) -> Result<(), BusFault> {
+        let transfers: heapless::Vec<u32, 4> = self.transfers().collect();
+        for address in transfers {
+            let mask = self.lane_mask(address);
+            self.memory.write_lanes(address, mask, value)?;
+        }
+        self.increment();
+        Ok(())
+    }
+}
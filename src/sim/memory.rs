@@ -0,0 +1,86 @@
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use super::cortexm::CoreSim;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BusFault;
+
+/// Sparse little-endian memory, unwritten locations read as zero.
+#[derive(Debug, Clone, Default)]
+pub struct Memory {
+    words: BTreeMap<u32, u32>,
+    faults: Vec<Range<u32>>,
+    core: Option<CoreSim>,
+}
+
+impl Memory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Put the debug registers of `core` in the SCS.
+    pub fn with_core(mut self, core: CoreSim) -> Self {
+        self.core = Some(core);
+        self
+    }
+
+    pub fn core(&self) -> Option<&CoreSim> {
+        self.core.as_ref()
+    }
+
+    pub fn core_mut(&mut self) -> Option<&mut CoreSim> {
+        self.core.as_mut()
+    }
+
+    /// Make every access to `range` end in a bus error.
+    pub fn add_fault_region(&mut self, range: Range<u32>) {
+        self.faults.push(range);
+    }
+
+    fn check(&self, address: u32) -> Result<(), BusFault> {
+        match self.faults.iter().any(|range| range.contains(&address)) {
+            true => Err(BusFault),
+            false => Ok(()),
+        }
+    }
+
+    pub fn read_word(&mut self, address: u32) -> Result<u32, BusFault> {
+        let address = address & !0x3;
+        self.check(address)?;
+        if let Some(core) = self.core.as_mut().filter(|_| CoreSim::maps(address)) {
+            return Ok(core.read(address));
+        }
+        Ok(self.words.get(&address).copied().unwrap_or(0))
+    }
+
+    pub fn write_word(&mut self, address: u32, value: u32) -> Result<(), BusFault> {
+        self.write_lanes(address & !0x3, 0xffffffff, value)
+    }
+
+    /// Write only the bits of `value` selected by `mask` in the word at `address`.
+    pub fn write_lanes(&mut self, address: u32, mask: u32, value: u32) -> Result<(), BusFault> {
+        let address = address & !0x3;
+        self.check(address)?;
+        if let Some(core) = self.core.as_mut().filter(|_| CoreSim::maps(address)) {
+            core.write(address, value & mask);
+            return Ok(());
+        }
+        let old = self.words.get(&address).copied().unwrap_or(0);
+        self.words.insert(address, (old & !mask) | (value & mask));
+        Ok(())
+    }
+
+    pub fn load(&mut self, address: u32, data: &[u8]) {
+        for (i, &byte) in data.iter().enumerate() {
+            let address = address + i as u32;
+            let shift = (address & 0x3) * 8;
+            let old = self.words.get(&(address & !0x3)).copied().unwrap_or(0);
+            self.words.insert(
+                address & !0x3,
+                (old & !(0xff << shift)) | ((byte as u32) << shift),
+            );
+        }
+    }
+}
@@ -0,0 +1,548 @@
+//! Host-side SWD target simulation.
+//!
+//! [`SimIo`] implements [`SwdIo`] by decoding the wire protocol bit by bit
+//! and forwarding the decoded transactions to a [`SimTarget`]. This lets the
+//! whole stack above the pins run without an ESP32-C3 or a real chip.
+
+use crate::io::{SwdIo, Unsupported};
+#[cfg(test)]
+use crate::swd::Swd;
+use crate::swd::{
+    APnDP, Ack, RnW, JTAG_TO_DORMANT, SELECTION_ALERT, SWD_ACTIVATION_CODE, SWD_TO_DORMANT,
+};
+
+pub mod cortexm;
+pub use cortexm::CoreSim;
+
+pub mod memory;
+pub use memory::Memory;
+
+pub mod memap;
+pub use memap::MemApSim;
+
+pub mod target;
+pub use target::{Faults, Target};
+
+pub mod multidrop;
+pub use multidrop::MultiDrop;
+
+/// Register level model of a debug port behind the simulated wire.
+pub trait SimTarget {
+    /// Called when the wire sees a line reset.
+    fn line_reset(&mut self);
+
+    /// Decide the ack for a decoded request, `None` means no reply at all.
+    fn request(&mut self, apndp: APnDP, rnw: RnW, a: [bool; 2]) -> Option<Ack>;
+
+    /// Data phase of an acknowledged read.
+    fn read(&mut self, apndp: APnDP, a: [bool; 2]) -> u32;
+
+    /// Data phase of an acknowledged write with correct parity.
+    fn write(&mut self, apndp: APnDP, a: [bool; 2], value: u32);
+
+    /// Data phase of an acknowledged write with bad parity.
+    fn write_parity_error(&mut self) {}
+
+    /// Called when the host pulls nRESET low or releases it.
+    fn set_nreset(&mut self, _asserted: bool) {}
+
+    /// Turnaround period in clock cycles.
+    fn turnaround(&mut self) -> u8 {
+        1
+    }
+
+    /// Whether WAIT and FAULT acks are followed by a data phase.
+    fn data_phase_on_error(&mut self) -> bool {
+        false
+    }
+
+    /// Whether to send a wrong parity bit with the current read data.
+    fn read_parity_error(&mut self) -> bool {
+        false
+    }
+}
+
+const JTAG_TO_SWD: u16 = 0xe79e;
+const LINE_RESET_CLOCKS: usize = 50;
+const JTAG_ACTIVATION_CODE: u8 = 0x00;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Protocol {
+    Jtag,
+    Swd,
+    /// SWJ-DP v2 dormant state, `activation` counts the bits received after
+    /// a Selection Alert.
+    Dormant {
+        activation: Option<(u8, u16)>,
+    },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Request {
+    apndp: APnDP,
+    rnw: RnW,
+    a: [bool; 2],
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Phase {
+    /// Protocol error, wait for a line reset.
+    Lockout,
+    /// At least 50 high clocks seen, wait for the first low clock.
+    LineReset,
+    Idle,
+    Request {
+        bits: u8,
+        count: u8,
+    },
+    Turnaround {
+        cycles: u8,
+        next: Next,
+    },
+    Ack {
+        ack: Ack,
+        index: u8,
+        request: Request,
+    },
+    ReadData {
+        data: u64,
+        index: u8,
+    },
+    WriteData {
+        data: u64,
+        count: u8,
+        request: Request,
+    },
+    /// Clock cycles ignored by the target.
+    Skip {
+        cycles: u8,
+        next: Next,
+    },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Next {
+    Idle,
+    Turnaround,
+    /// Data phase nobody uses.
+    Skip,
+    Ack(Ack, Request),
+    WriteData(Request),
+}
+
+pub struct SimIo<T> {
+    pub target: T,
+    /// Accept `shift_out`/`shift_in` like a backend with a hardware shifter,
+    /// so both transfer paths of `Swd` can be run against the same target.
+    pub shifter: bool,
+    protocol: Protocol,
+    phase: Phase,
+    /// Last 128 bits seen by the target, newest in the top bit.
+    history: u128,
+    high_clocks: usize,
+    swclk: bool,
+    host_level: bool,
+    host_driving: bool,
+    target_level: Option<bool>,
+    nreset: bool,
+}
+
+impl<T: SimTarget> SimIo<T> {
+    pub fn new(target: T) -> Self {
+        Self {
+            target,
+            shifter: false,
+            protocol: Protocol::Jtag,
+            phase: Phase::Lockout,
+            history: 0,
+            high_clocks: 0,
+            swclk: false,
+            host_level: false,
+            host_driving: false,
+            target_level: None,
+            nreset: false,
+        }
+    }
+
+    /// Start in dormant state like an SWJ-DP v2, instead of JTAG.
+    pub fn dormant(mut self) -> Self {
+        self.protocol = Protocol::Dormant { activation: None };
+        self
+    }
+
+    /// The last `bits` bits seen by the target, oldest in bit 0.
+    fn last(&self, bits: u32) -> u128 {
+        self.history >> (128 - bits)
+    }
+
+    fn rising_edge(&mut self) {
+        // Level seen by the target, SWDIO is pulled up when nobody drives it.
+        let bit = !self.host_driving || self.host_level;
+        self.history = (self.history >> 1) | ((bit as u128) << 127);
+
+        match self.protocol {
+            Protocol::Jtag => {
+                if self.last(16) == JTAG_TO_SWD.into() {
+                    self.protocol = Protocol::Swd;
+                    self.phase = Phase::Lockout;
+                } else if self.last(31) == JTAG_TO_DORMANT.into() {
+                    self.protocol = Protocol::Dormant { activation: None };
+                }
+                return;
+            }
+            Protocol::Dormant { activation } => {
+                self.protocol = self.dormant_bit(activation, bit);
+                if self.protocol == Protocol::Swd {
+                    self.phase = Phase::Lockout;
+                }
+                return;
+            }
+            Protocol::Swd => {
+                // A line reset followed by the SWD-to-dormant sequence.
+                let line_reset = (1u128 << LINE_RESET_CLOCKS) - 1;
+                if self.last(LINE_RESET_CLOCKS as u32 + 16)
+                    == line_reset | (u128::from(SWD_TO_DORMANT) << LINE_RESET_CLOCKS)
+                {
+                    self.protocol = Protocol::Dormant { activation: None };
+                    self.target_level = None;
+                    return;
+                }
+            }
+        }
+
+        if self.host_driving && bit {
+            self.high_clocks += 1;
+            if self.high_clocks == LINE_RESET_CLOCKS {
+                self.target_level = None;
+                self.target.line_reset();
+                self.phase = Phase::LineReset;
+                return;
+            }
+        } else {
+            self.high_clocks = 0;
+        }
+
+        self.phase = match self.phase {
+            Phase::Lockout => Phase::Lockout,
+            Phase::LineReset if bit => Phase::LineReset,
+            Phase::LineReset | Phase::Idle if !bit => Phase::Idle,
+            Phase::LineReset | Phase::Idle => Phase::Request { bits: 1, count: 1 },
+            Phase::Request { bits, count } => {
+                let bits = bits | ((bit as u8) << count);
+                match count + 1 {
+                    8 => self.decode_request(bits),
+                    count => Phase::Request { bits, count },
+                }
+            }
+            Phase::Turnaround { cycles, next } if cycles > 1 => Phase::Turnaround {
+                cycles: cycles - 1,
+                next,
+            },
+            Phase::Turnaround { next, .. } => self.enter(next),
+            Phase::Skip { cycles, next } if cycles > 1 => Phase::Skip {
+                cycles: cycles - 1,
+                next,
+            },
+            Phase::Skip { next, .. } => self.enter(next),
+            Phase::Ack {
+                ack,
+                index,
+                request,
+            } if index < 3 => {
+                self.target_level = Some(<[bool; 3]>::from(ack)[index as usize]);
+                Phase::Ack {
+                    ack,
+                    index: index + 1,
+                    request,
+                }
+            }
+            Phase::Ack { ack, request, .. } => {
+                self.target_level = None;
+                match (ack, request.rnw) {
+                    (Ack::Ok, RnW::Read) => {
+                        let value = self.target.read(request.apndp, request.a);
+                        let parity =
+                            (value.count_ones() & 1 == 1) ^ self.target.read_parity_error();
+                        let data = value as u64 | ((parity as u64) << 32);
+                        self.target_level = Some(data & 1 == 1);
+                        Phase::ReadData { data, index: 1 }
+                    }
+                    (Ack::Ok, RnW::Write) => Phase::Turnaround {
+                        cycles: self.target.turnaround(),
+                        next: Next::WriteData(request),
+                    },
+                    // Overrun detection keeps the data phase after WAIT and FAULT.
+                    (_, RnW::Read) if self.target.data_phase_on_error() => Phase::Skip {
+                        cycles: 33,
+                        next: Next::Turnaround,
+                    },
+                    (_, RnW::Write) if self.target.data_phase_on_error() => Phase::Turnaround {
+                        cycles: self.target.turnaround(),
+                        next: Next::Skip,
+                    },
+                    _ => Phase::Turnaround {
+                        cycles: self.target.turnaround(),
+                        next: Next::Idle,
+                    },
+                }
+            }
+            Phase::ReadData { data, index } if index < 33 => {
+                self.target_level = Some((data >> index) & 1 == 1);
+                Phase::ReadData {
+                    data,
+                    index: index + 1,
+                }
+            }
+            Phase::ReadData { .. } => {
+                self.target_level = None;
+                Phase::Turnaround {
+                    cycles: self.target.turnaround(),
+                    next: Next::Idle,
+                }
+            }
+            Phase::WriteData {
+                data,
+                count,
+                request,
+            } => {
+                let data = data | ((bit as u64) << count);
+                if count + 1 < 33 {
+                    Phase::WriteData {
+                        data,
+                        count: count + 1,
+                        request,
+                    }
+                } else {
+                    let value = data as u32;
+                    let parity = value.count_ones() & 1 == 1;
+                    if parity == (data >> 32 == 1) {
+                        self.target.write(request.apndp, request.a, value);
+                    } else {
+                        self.target.write_parity_error();
+                    }
+                    Phase::Idle
+                }
+            }
+        };
+    }
+
+    fn dormant_bit(&mut self, activation: Option<(u8, u16)>, bit: bool) -> Protocol {
+        let Some((count, code)) = activation else {
+            let activation = (self.history == SELECTION_ALERT).then_some((0, 0));
+            return Protocol::Dormant { activation };
+        };
+        let code = code | ((bit as u16) << count);
+        if count + 1 < 12 {
+            return Protocol::Dormant {
+                activation: Some((count + 1, code)),
+            };
+        }
+        // Four low cycles, then the 8-bit activation code.
+        match ((code & 0xf), (code >> 4) as u8) {
+            (0, SWD_ACTIVATION_CODE) => Protocol::Swd,
+            (0, JTAG_ACTIVATION_CODE) => Protocol::Jtag,
+            _ => Protocol::Dormant { activation: None },
+        }
+    }
+
+    fn enter(&mut self, next: Next) -> Phase {
+        match next {
+            Next::Idle => Phase::Idle,
+            Next::Ack(ack, request) => {
+                self.target_level = Some(<[bool; 3]>::from(ack)[0]);
+                Phase::Ack {
+                    ack,
+                    index: 1,
+                    request,
+                }
+            }
+            Next::WriteData(request) => Phase::WriteData {
+                data: 0,
+                count: 0,
+                request,
+            },
+            Next::Turnaround => Phase::Turnaround {
+                cycles: self.target.turnaround(),
+                next: Next::Idle,
+            },
+            Next::Skip => Phase::Skip {
+                cycles: 33,
+                next: Next::Idle,
+            },
+        }
+    }
+
+    fn decode_request(&mut self, bits: u8) -> Phase {
+        let bit = |i: u8| (bits >> i) & 1 == 1;
+        let request = Request {
+            apndp: bit(1).into(),
+            rnw: bit(2).into(),
+            a: [bit(3), bit(4)],
+        };
+        let parity = bit(1) ^ bit(2) ^ bit(3) ^ bit(4);
+        if parity != bit(5) || bit(6) || !bit(7) {
+            return Phase::Lockout;
+        }
+        // TARGETSEL is never acknowledged, the data follows turnaround, the
+        // undriven ack and another turnaround.
+        if (request.apndp, request.rnw, request.a) == (APnDP::DP, RnW::Write, [true, true]) {
+            return Phase::Skip {
+                cycles: 2 * self.target.turnaround() + 3,
+                next: Next::WriteData(request),
+            };
+        }
+        match self.target.request(request.apndp, request.rnw, request.a) {
+            Some(ack) => Phase::Turnaround {
+                cycles: self.target.turnaround(),
+                next: Next::Ack(ack, request),
+            },
+            None => Phase::Lockout,
+        }
+    }
+}
+
+impl<T: SimTarget> SwdIo for SimIo<T> {
+    fn set_swclk(&mut self, high: bool) {
+        if high && !self.swclk {
+            self.rising_edge();
+        }
+        self.swclk = high;
+    }
+
+    fn set_swdio(&mut self, high: bool) {
+        self.host_level = high;
+    }
+
+    fn swdio(&mut self) -> bool {
+        match self.target_level {
+            Some(level) => level,
+            None => !self.host_driving || self.host_level,
+        }
+    }
+
+    fn swclk_as_output(&mut self) {}
+
+    fn swdio_as_output(&mut self) {
+        self.host_driving = true;
+    }
+
+    fn swdio_as_input(&mut self) {
+        self.host_driving = false;
+    }
+
+    fn set_nreset(&mut self, asserted: bool) -> Result<(), Unsupported> {
+        if asserted != self.nreset {
+            self.nreset = asserted;
+            self.target.set_nreset(asserted);
+        }
+        Ok(())
+    }
+
+    fn nreset(&mut self) -> Result<bool, Unsupported> {
+        Ok(self.nreset)
+    }
+
+    fn shift_out(&mut self, value: u32, length: usize) -> Result<(), Unsupported> {
+        if !self.shifter || !self.host_driving {
+            return Err(Unsupported);
+        }
+        for i in 0..length {
+            self.set_swdio((value >> i) & 1 == 1);
+            self.set_swclk(true);
+            self.set_swclk(false);
+        }
+        Ok(())
+    }
+
+    fn shift_in(&mut self, length: usize) -> Result<u32, Unsupported> {
+        if !self.shifter || self.host_driving {
+            return Err(Unsupported);
+        }
+        let mut value = 0;
+        for i in 0..length {
+            value |= (self.swdio() as u32) << i;
+            self.set_swclk(true);
+            self.set_swclk(false);
+        }
+        Ok(value)
+    }
+}
+
+/// Poll `future` on the calling thread until it completes.
+///
+/// Nothing in the simulation waits for anything but embassy-time timers,
+/// which expire on their own with the `std` time driver, so the waker is
+/// never needed.
+#[cfg(test)]
+pub(crate) fn block_on<F: core::future::Future>(future: F) -> F::Output {
+    use core::task::{Context, Poll, Waker};
+
+    let mut future = core::pin::pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+/// An ARM SW-DP in JTAG mode with one AHB-AP in front of empty memory.
+#[cfg(test)]
+pub(crate) fn swd() -> Swd<SimIo<Target>> {
+    let ap = MemApSim::new(0xe00ff003, Memory::new());
+    Swd::new(SimIo::new(Target::new(IDCODE).with_ap(ap)))
+}
+
+/// DPIDR of the target built by [`swd`].
+#[cfg(test)]
+pub(crate) const IDCODE: u32 = 0x2ba01477;
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::registers::dp::Idcode;
+
+    /// Run the same transactions through `Swd` and return every result,
+    /// each with the last bits the target saw by then.
+    fn transactions(shifter: bool) -> Vec<(u32, u128)> {
+        let mut swd = swd();
+        swd.io.shifter = shifter;
+        let mut trace = Vec::new();
+        block_on(async {
+            swd.reset().await.unwrap();
+            trace.push((0, swd.io.history));
+            let idcode = swd.read_dp_register::<Idcode>().await.unwrap();
+            trace.push((idcode.into(), swd.io.history));
+            let ctrlstat = swd.power_up().await.unwrap();
+            trace.push((ctrlstat.into(), swd.io.history));
+            let idr = swd.read_ap(0, 0xfc).await.unwrap();
+            trace.push((idr, swd.io.history));
+            let values = [0x0123_4567, 0x89ab_cdef, 0xffff_ffff, 0];
+            swd.memap(0)
+                .write_block(0x2000_0000, &values)
+                .await
+                .unwrap();
+            trace.push((0, swd.io.history));
+            let mut read = [0; 4];
+            swd.memap(0)
+                .read_block(0x2000_0000, &mut read)
+                .await
+                .unwrap();
+            for value in read {
+                trace.push((value, swd.io.history));
+            }
+        });
+        trace
+    }
+
+    #[test]
+    fn shifter_matches_bit_bang() {
+        let trace = transactions(false);
+        assert_eq!(trace[1].0, IDCODE);
+        let read: Vec<u32> = trace[5..].iter().map(|&(value, _)| value).collect();
+        assert_eq!(read, [0x0123_4567, 0x89ab_cdef, 0xffff_ffff, 0]);
+        assert_eq!(transactions(true), trace);
+    }
+}
@@ -0,0 +1,77 @@
+use alloc::vec::Vec;
+
+use crate::swd::{APnDP, Ack, RnW};
+
+use super::{SimTarget, Target};
+
+/// Several targets sharing one SWD bus, told apart with TARGETSEL.
+///
+/// Every target sees every request, a request answered by more than one
+/// target is treated as no reply since the acks would collide on the wire.
+pub struct MultiDrop {
+    pub targets: Vec<Target>,
+}
+
+impl MultiDrop {
+    pub fn new(targets: Vec<Target>) -> Self {
+        Self { targets }
+    }
+
+    fn selected(&mut self) -> impl Iterator<Item = &mut Target> {
+        self.targets.iter_mut().filter(|target| target.selected)
+    }
+}
+
+impl SimTarget for MultiDrop {
+    fn line_reset(&mut self) {
+        self.targets.iter_mut().for_each(Target::line_reset);
+    }
+
+    fn request(&mut self, apndp: APnDP, rnw: RnW, a: [bool; 2]) -> Option<Ack> {
+        let mut selected = self.selected();
+        match (selected.next(), selected.next()) {
+            (Some(target), None) => target.request(apndp, rnw, a),
+            _ => None,
+        }
+    }
+
+    fn read(&mut self, apndp: APnDP, a: [bool; 2]) -> u32 {
+        self.selected()
+            .next()
+            .map_or(0, |target| target.read(apndp, a))
+    }
+
+    fn write(&mut self, apndp: APnDP, a: [bool; 2], value: u32) {
+        // TARGETSEL reaches every target that has not been deselected yet.
+        self.selected()
+            .for_each(|target| target.write(apndp, a, value));
+    }
+
+    fn set_nreset(&mut self, asserted: bool) {
+        self.targets
+            .iter_mut()
+            .for_each(|target| target.set_nreset(asserted));
+    }
+
+    fn write_parity_error(&mut self) {
+        self.selected().for_each(Target::write_parity_error);
+    }
+
+    fn turnaround(&mut self) -> u8 {
+        self.selected()
+            .next()
+            .map_or(1, |target| target.turnaround())
+    }
+
+    fn data_phase_on_error(&mut self) -> bool {
+        self.selected()
+            .next()
+            .is_some_and(|target| target.data_phase_on_error())
+    }
+
+    fn read_parity_error(&mut self) -> bool {
+        self.selected()
+            .next()
+            .is_some_and(|target| target.read_parity_error())
+    }
+}
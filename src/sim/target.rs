@@ -0,0 +1,322 @@
+use alloc::vec::Vec;
+
+use crate::registers::dp::{CtrlStat, Dlcr, Select};
+use crate::swd::{APnDP, Ack, RnW};
+
+use super::cortexm::CoreSim;
+use super::memap::MemApSim;
+use super::SimTarget;
+
+/// Faults to inject, each counter is the number of upcoming events affected.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Faults {
+    /// Answer the next requests with WAIT.
+    pub wait: usize,
+    /// Flip the parity bit of the next read data phases.
+    pub parity: usize,
+    /// Do not drive the ack of the next requests.
+    pub no_reply: usize,
+    /// Answer the next AP requests with FAULT, setting STICKYERR.
+    pub fault: usize,
+    /// Answer this many requests normally before the injected WAIT, FAULT
+    /// or missing acks start.
+    pub skip: usize,
+}
+
+/// Software model of an ADIv5 SW-DP with MEM-APs behind it.
+pub struct Target {
+    pub idcode: u32,
+    pub ctrlstat: CtrlStat,
+    pub dlcr: Dlcr,
+    pub select: Select,
+    pub select1: u32,
+    pub rdbuff: u32,
+    pub aps: Vec<MemApSim>,
+    pub faults: Faults,
+    /// TARGETID for SWD protocol version 2, `None` for a version 1 DP.
+    pub targetid: Option<u32>,
+    pub instance: u8,
+    /// Cleared by a TARGETSEL write for another target until the next line reset.
+    pub selected: bool,
+    /// BASEPTR of an ADIv6 DP, which addresses AP `n` at `n << 12`. `None`
+    /// for ADIv5 APSEL addressing.
+    pub baseptr: Option<u64>,
+    /// A system reset of a core behind one of the APs, or nRESET, also
+    /// resets the DP, which stops answering until the next line reset.
+    pub reset_drops_link: bool,
+    /// Number of SELECT writes, which the host caches.
+    pub select_writes: usize,
+}
+
+impl Target {
+    pub fn new(idcode: u32) -> Self {
+        Self {
+            idcode,
+            ctrlstat: CtrlStat::default(),
+            dlcr: Dlcr::default().set_wiremode(1),
+            select: Select::default(),
+            select1: 0,
+            rdbuff: 0,
+            aps: Vec::new(),
+            faults: Faults::default(),
+            targetid: None,
+            instance: 0,
+            selected: true,
+            baseptr: None,
+            reset_drops_link: false,
+            select_writes: 0,
+        }
+    }
+
+    pub fn with_baseptr(mut self, baseptr: u64) -> Self {
+        self.baseptr = Some(baseptr);
+        self
+    }
+
+    pub fn with_targetid(mut self, targetid: u32, instance: u8) -> Self {
+        self.targetid = Some(targetid);
+        self.instance = instance;
+        self
+    }
+
+    pub fn with_ap(mut self, ap: MemApSim) -> Self {
+        self.aps.push(ap);
+        self
+    }
+
+    /// Reset the DP, which stops answering until the next line reset.
+    fn drop_link(&mut self) {
+        self.selected = false;
+        self.select = Select::default();
+        self.ctrlstat = CtrlStat::default();
+    }
+
+    fn sticky(&self) -> bool {
+        self.ctrlstat.stickyerr() || self.ctrlstat.stickyorun() || self.ctrlstat.wdataerr()
+    }
+
+    fn read_dp(&mut self, a: [bool; 2]) -> u32 {
+        match a {
+            [false, false] => match (self.baseptr, self.select.dpbanksel()) {
+                (Some(baseptr), 2) => (baseptr as u32 & 0xffff_f000) | 1,
+                (Some(baseptr), 3) => (baseptr >> 32) as u32,
+                _ => self.idcode,
+            },
+            [true, false] => match self.select.dpbanksel() {
+                0 => self.ctrlstat.into(),
+                1 => self.dlcr.into(),
+                2 => self.targetid.unwrap_or(0),
+                // Protocol version 1 is SWD protocol version 2.
+                3 if self.targetid.is_some() => ((self.instance as u32) << 28) | 1,
+                5 => self.select1,
+                _ => 0,
+            },
+            [false, true] | [true, true] => self.rdbuff,
+        }
+    }
+
+    fn write_dp(&mut self, a: [bool; 2], value: u32) {
+        match a {
+            [false, false] => self.abort(value),
+            [true, false] if self.select.dpbanksel() == 1 => {
+                // Only TURNROUND is writable.
+                self.dlcr = self.dlcr.set_turnround(Dlcr::from(value).turnround());
+            }
+            [true, false] if self.select.dpbanksel() == 5 => self.select1 = value,
+            [true, false] => {
+                let req = CtrlStat::from(value);
+                if req.cdbgrstreq() && !self.ctrlstat.cdbgrstreq() {
+                    self.aps
+                        .iter_mut()
+                        .filter_map(|ap| ap.memory.core_mut())
+                        .for_each(CoreSim::debug_reset);
+                }
+                // Sticky flags are cleared through ABORT, not by writing CTRL/STAT.
+                self.ctrlstat = req
+                    .set_cdbgpwrupack(req.cdbgpwrupreq())
+                    .set_csyspwrupack(req.csyspwrupreq())
+                    .set_cdbgrstack(req.cdbgrstreq())
+                    .set_stickyerr(self.ctrlstat.stickyerr())
+                    .set_stickyorun(self.ctrlstat.stickyorun())
+                    .set_stickycmp(self.ctrlstat.stickycmp())
+                    .set_wdataerr(self.ctrlstat.wdataerr())
+                    .set_readok(self.ctrlstat.readok());
+            }
+            [false, true] => {
+                self.select = value.into();
+                self.select_writes += 1;
+            }
+            [true, true] => {
+                if let Some(targetid) = self.targetid {
+                    let id = (targetid & 0x0fff_ffff) | ((self.instance as u32) << 28) | 1;
+                    self.selected = value == id;
+                }
+            }
+        }
+    }
+
+    fn abort(&mut self, value: u32) {
+        if value & (1 << 0) != 0 {
+            self.faults.wait = 0;
+        }
+        if value & (1 << 1) != 0 {
+            self.ctrlstat = self.ctrlstat.set_stickycmp(false);
+        }
+        if value & (1 << 2) != 0 {
+            self.ctrlstat = self.ctrlstat.set_stickyerr(false);
+        }
+        if value & (1 << 3) != 0 {
+            self.ctrlstat = self.ctrlstat.set_wdataerr(false);
+        }
+        if value & (1 << 4) != 0 {
+            self.ctrlstat = self.ctrlstat.set_stickyorun(false);
+        }
+    }
+
+    /// AP index and ADIv5 register address selected for an AP access.
+    fn ap_register(&self, a: [bool; 2]) -> Option<(usize, u8)> {
+        let offset = ((a[1] as u8) << 3) | ((a[0] as u8) << 2);
+        if self.baseptr.is_none() {
+            let addr = (self.select.apbanksel() << 4) | offset;
+            return Some((self.select.apsel() as usize, addr));
+        }
+        let address = (u64::from(self.select1) << 32)
+            | u64::from(self.select.addr() << 4)
+            | u64::from(offset);
+        // Only the ADIv5 registers at 0xd00 of the AP window are implemented.
+        match address & 0xfff {
+            0xd00..=0xdff => Some(((address >> 12) as usize, address as u8)),
+            _ => None,
+        }
+    }
+
+    fn read_ap(&mut self, a: [bool; 2]) -> u32 {
+        let result = match self.ap_register(a) {
+            Some((ap, addr)) => match self.aps.get_mut(ap) {
+                Some(ap) => ap.read(addr),
+                None => Ok(0),
+            },
+            None => Ok(0),
+        };
+        self.ctrlstat = self.ctrlstat.set_readok(result.is_ok());
+        result.unwrap_or_else(|_| {
+            self.ctrlstat = self.ctrlstat.set_stickyerr(true);
+            0
+        })
+    }
+
+    fn write_ap(&mut self, a: [bool; 2], value: u32) {
+        let Some((ap, addr)) = self.ap_register(a) else {
+            return;
+        };
+        let Some(ap) = self.aps.get_mut(ap) else {
+            return;
+        };
+        if ap.write(addr, value).is_err() {
+            self.ctrlstat = self.ctrlstat.set_stickyerr(true);
+        }
+        let system_reset = ap
+            .memory
+            .core_mut()
+            .is_some_and(|core| core.take_system_reset());
+        if system_reset && self.reset_drops_link {
+            self.drop_link();
+        }
+    }
+}
+
+impl SimTarget for Target {
+    fn line_reset(&mut self) {
+        self.select = Select::default();
+        self.selected = true;
+    }
+
+    fn request(&mut self, apndp: APnDP, rnw: RnW, a: [bool; 2]) -> Option<Ack> {
+        if !self.selected {
+            return None;
+        }
+        let inject = self.faults.skip == 0;
+        self.faults.skip = self.faults.skip.saturating_sub(1);
+        if inject && self.faults.no_reply > 0 {
+            self.faults.no_reply -= 1;
+            return None;
+        }
+        // ABORT writes are accepted even while a transaction is stalled.
+        if (apndp, rnw, a) == (APnDP::DP, RnW::Write, [false, false]) {
+            return Some(Ack::Ok);
+        }
+        if inject && self.faults.wait > 0 {
+            self.faults.wait -= 1;
+            if self.ctrlstat.orundetect() {
+                self.ctrlstat = self.ctrlstat.set_stickyorun(true);
+            }
+            return Some(Ack::Wait);
+        }
+        if inject && self.faults.fault > 0 && apndp == APnDP::AP {
+            self.faults.fault -= 1;
+            self.ctrlstat = self.ctrlstat.set_stickyerr(true);
+            return Some(Ack::Fault);
+        }
+        let allowed = match (apndp, rnw, a) {
+            (APnDP::AP, _, _) => false,
+            (APnDP::DP, RnW::Read, [false, false] | [true, false] | [false, true]) => true,
+            _ => false,
+        };
+        if self.sticky() && !allowed {
+            if self.ctrlstat.orundetect() {
+                self.ctrlstat = self.ctrlstat.set_stickyorun(true);
+            }
+            return Some(Ack::Fault);
+        }
+        Some(Ack::Ok)
+    }
+
+    fn read(&mut self, apndp: APnDP, a: [bool; 2]) -> u32 {
+        match apndp {
+            APnDP::DP => self.read_dp(a),
+            APnDP::AP => {
+                // AP reads are posted, the result shows up in RDBUFF.
+                let value = self.read_ap(a);
+                core::mem::replace(&mut self.rdbuff, value)
+            }
+        }
+    }
+
+    fn write(&mut self, apndp: APnDP, a: [bool; 2], value: u32) {
+        match apndp {
+            APnDP::DP => self.write_dp(a, value),
+            APnDP::AP => self.write_ap(a, value),
+        }
+    }
+
+    fn set_nreset(&mut self, asserted: bool) {
+        for ap in &mut self.aps {
+            if let Some(core) = ap.memory.core_mut() {
+                core.set_nreset(asserted);
+            }
+        }
+        if asserted && self.reset_drops_link {
+            self.drop_link();
+        }
+    }
+
+    fn write_parity_error(&mut self) {
+        self.ctrlstat = self.ctrlstat.set_wdataerr(true);
+    }
+
+    fn turnaround(&mut self) -> u8 {
+        self.dlcr.turnround() + 1
+    }
+
+    fn data_phase_on_error(&mut self) -> bool {
+        self.ctrlstat.orundetect()
+    }
+
+    fn read_parity_error(&mut self) -> bool {
+        if self.faults.parity > 0 {
+            self.faults.parity -= 1;
+            return true;
+        }
+        false
+    }
+}
@@ -1,52 +1,181 @@
 use core::array::from_fn;
 
-use embassy_time::Timer;
-use esp_hal::gpio::AnyPin;
-use esp_hal::gpio::Pull;
-
-use esp_hal::gpio::Flex;
-use esp_hal::peripheral::Peripheral;
+use embassy_time::{Duration, Instant};
 use log::info;
 use log::trace;
 use thiserror::Error;
 
+use crate::ap::ApAddress;
+use crate::clock::{SwdClock, DEFAULT_FREQUENCY, MAX_FREQUENCY, MIN_FREQUENCY};
+use crate::io::SwdIo;
 use crate::registers::ap;
 use crate::registers::dp;
-use crate::registers::dp::{RdBuff, Select};
+use crate::registers::dp::{
+    Abort, CtrlStat, DPRegister, Dlcr, Idcode, RdBuff, Select, Select1, TargetSel,
+};
+
+pub const DEFAULT_WAIT_RETRIES: usize = 10;
+pub const DEFAULT_WAIT_TIMEOUT: Duration = Duration::from_millis(100);
+/// How long `power_up` and `debug_reset` wait for CTRL/STAT acknowledges.
+pub const POWER_UP_TIMEOUT: Duration = Duration::from_millis(100);
+/// How long `set_frequency` clocks the idle line for each measurement.
+const MEASURE_TIME: Duration = Duration::from_millis(2);
+
+/// Selection Alert sequence that wakes an SWJ-DP v2 from dormant state.
+pub const SELECTION_ALERT: u128 = 0x19bc0ea2_e3ddafe9_86852d95_6209f392;
+/// Activation code selecting SWD after the Selection Alert.
+pub const SWD_ACTIVATION_CODE: u8 = 0x1a;
+/// 31-bit sequence that puts a JTAG SWJ-DP v2 into dormant state.
+pub const JTAG_TO_DORMANT: u32 = 0x33bbbd99;
+/// Sequence that puts an SWD SWJ-DP v2 into dormant state after a line reset.
+pub const SWD_TO_DORMANT: u16 = 0xe3bc;
+
+/// How `Swd::reset` brings the target into SWD mode.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Wakeup {
+    /// The 16-bit JTAG-to-SWD switch of SWJ-DP v1.
+    Legacy,
+    /// Selection Alert and SWD activation code, for targets in dormant state.
+    Dormant,
+}
+
+/// Wire settings the host and the DP have to agree on.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct LineConfig {
+    /// Turnaround period in clock cycles, 1 to 4 (DLCR.TURNROUND + 1).
+    pub turnaround: u8,
+    /// WAIT and FAULT acks are followed by a data phase, as with
+    /// CTRL/STAT.ORUNDETECT set.
+    pub data_phase: bool,
+}
 
-pub struct Swd<'a> {
-    pub swclk: Flex<'a>,
-    pub swdio: Flex<'a>,
+impl Default for LineConfig {
+    fn default() -> Self {
+        Self {
+            turnaround: 1,
+            data_phase: false,
+        }
+    }
 }
 
-impl<'a> Swd<'a> {
-    pub fn new(
-        swclk: impl Peripheral<P = impl Into<AnyPin>> + 'a,
-        swdio: impl Peripheral<P = impl Into<AnyPin>> + 'a,
-    ) -> Self {
-        let mut swclk = Flex::new(swclk);
-        let mut swdio = Flex::new(swdio);
-        swclk.set_as_output();
-        swdio.set_as_output();
-        Self { swclk, swdio }
+pub struct Swd<Io> {
+    pub io: Io,
+    clock: SwdClock,
+    frequency: u32,
+    /// Last value written to the DP SELECT register, if known.
+    select: Option<Select>,
+    /// Last value written to SELECT1, the upper half of ADIv6 AP addresses.
+    select1: Option<u32>,
+    wait_retries: usize,
+    wait_timeout: Duration,
+    /// CTRL/STAT.ORUNDETECT as last written, see `set_overrun_detect`.
+    overrun_detect: bool,
+    line: LineConfig,
+    /// Target selected on a multi-drop bus, re-selected after every reset.
+    target: Option<TargetSel>,
+    /// Wakeup used by `reset`, `None` tries legacy and then dormant.
+    wakeup: Option<Wakeup>,
+}
+
+impl<Io: SwdIo> Swd<Io> {
+    pub fn new(mut io: Io) -> Self {
+        io.swclk_as_output();
+        io.swdio_as_output();
+        Self {
+            io,
+            clock: SwdClock::new(),
+            frequency: DEFAULT_FREQUENCY,
+            select: None,
+            select1: None,
+            wait_retries: DEFAULT_WAIT_RETRIES,
+            wait_timeout: DEFAULT_WAIT_TIMEOUT,
+            overrun_detect: false,
+            line: LineConfig::default(),
+            target: None,
+            wakeup: None,
+        }
+    }
+
+    pub fn line_config(&self) -> LineConfig {
+        self.line
+    }
+
+    /// Override the line configuration without touching the target, for a DP
+    /// that was configured by someone else. Writes to DLCR and CTRL/STAT
+    /// update it on their own.
+    pub fn set_line_config(&mut self, line: LineConfig) {
+        self.line = LineConfig {
+            turnaround: line.turnaround.clamp(1, 4),
+            ..line
+        };
+    }
+
+    /// Restrict `reset` to one wakeup sequence, or try both with `None`.
+    pub fn set_wakeup(&mut self, wakeup: Option<Wakeup>) {
+        self.wakeup = wakeup;
+    }
+
+    /// Give up on a transaction after `retries` WAIT acks or once `timeout`
+    /// has passed since the first attempt, whichever comes first.
+    pub fn set_wait_limits(&mut self, retries: usize, timeout: Duration) {
+        self.wait_retries = retries.max(1);
+        self.wait_timeout = timeout;
     }
 
     pub async fn wait_clock(&self) {
-        Timer::after_nanos(250).await
+        self.clock.wait().await
+    }
+
+    /// SWCLK frequency measured by the last `set_frequency`.
+    pub fn frequency(&self) -> u32 {
+        self.frequency
+    }
+
+    /// Set the SWCLK frequency in Hz, clamped to `MIN_FREQUENCY` to
+    /// `MAX_FREQUENCY`, and return the frequency actually achieved.
+    ///
+    /// The line is clocked idle while measuring, so this must only be called
+    /// between transactions.
+    pub async fn set_frequency(&mut self, hz: u32) -> u32 {
+        let hz = hz.clamp(MIN_FREQUENCY, MAX_FREQUENCY);
+        let half_period = 500_000_000 / hz;
+        self.io.set_frequency(hz);
+        self.clock.set_half_period(half_period);
+        let period = self.measure_period().await;
+        self.clock
+            .compensate(period.saturating_sub(2 * half_period) / 2);
+        let period = self.measure_period().await;
+        self.frequency = 1_000_000_000 / period.max(1);
+        info!("SWCLK set to {} Hz, got {} Hz", hz, self.frequency);
+        self.frequency
+    }
+
+    /// Average SWCLK period in ns over `MEASURE_TIME`.
+    async fn measure_period(&mut self) -> u32 {
+        let start = Instant::now();
+        let mut cycles = 0;
+        while cycles == 0 || start.elapsed() < MEASURE_TIME {
+            self.swd_clock(false).await;
+            cycles += 1;
+        }
+        (start.elapsed().as_micros() * 1000 / cycles) as u32
     }
 
     pub async fn swd_clock(&mut self, out: bool) -> bool {
-        let bit = self.swdio.level().into();
-        self.swdio.set_level(out.into());
+        let bit = self.io.swdio();
+        self.io.set_swdio(out);
         self.wait_clock().await;
-        self.swclk.set_high();
+        self.io.set_swclk(true);
         self.wait_clock().await;
-        self.swclk.set_low();
+        self.io.set_swclk(false);
         bit
     }
 
     pub async fn swj_sequence(&mut self, mut bit_len: u8, mut bits: u64) {
-        self.swdio.set_as_output();
+        // Arbitrary sequences may contain a line reset.
+        self.select = None;
+        self.select1 = None;
+        self.io.swdio_as_output();
         while bit_len != 0 {
             bit_len -= 1;
             self.swd_clock(bits & 1 == 1).await;
@@ -56,14 +185,18 @@ impl<'a> Swd<'a> {
 
     pub async fn turnaround_host(&mut self) {
         trace!("Turnaround to HOST");
-        self.swd_clock(false).await;
-        self.swdio.set_as_output();
+        for _ in 0..self.line.turnaround {
+            self.swd_clock(false).await;
+        }
+        self.io.swdio_as_output();
     }
 
     pub async fn turnaround_target(&mut self) {
         trace!("Turnaround to TARGET");
-        self.swdio.set_as_input(Pull::None);
-        self.swd_clock(false).await;
+        self.io.swdio_as_input();
+        for _ in 0..self.line.turnaround {
+            self.swd_clock(false).await;
+        }
     }
 
     pub async fn recv_bits(&mut self, bits: &mut [bool]) {
@@ -79,18 +212,36 @@ impl<'a> Swd<'a> {
     }
 
     pub async fn send_u32(&mut self, value: u32, length: usize) {
+        if self.io.shift_out(value, length).is_ok() {
+            return;
+        }
         let bits: [bool; u32::BITS as usize] = from_fn(|i| ((value >> i) & 1) == 1);
         self.send_bits(&bits[..length]).await
     }
 
+    pub async fn recv_u32(&mut self, length: usize) -> u32 {
+        if let Ok(value) = self.io.shift_in(length) {
+            return value;
+        }
+        let mut bits = [false; u32::BITS as usize];
+        self.recv_bits(&mut bits[..length]).await;
+        bits.iter()
+            .enumerate()
+            .fold(0, |n, (i, b)| n | ((*b as u32) << (i as u32)))
+    }
+
     pub async fn send_u16(&mut self, value: u16, length: usize) {
         let bits: [bool; u16::BITS as usize] = from_fn(|i| ((value >> i) & 1) == 1);
         self.send_bits(&bits[..length]).await
     }
 }
 
-impl Swd<'_> {
+impl<Io: SwdIo> Swd<Io> {
     pub async fn line_reset(&mut self, low_clocks: usize) {
+        self.select = None;
+        self.select1 = None;
+        // A request without reply leaves SWDIO as input.
+        self.io.swdio_as_output();
         self.send_bits(&[true; 50]).await;
         for _ in 0..low_clocks {
             self.swd_clock(false).await;
@@ -105,13 +256,172 @@ impl Swd<'_> {
         .await
     }
 
-    pub async fn reset(&mut self) {
-        trace!("Resetting SWD");
-        self.swclk.set_as_output();
-        self.swdio.set_as_output();
+    /// Put an SWJ-DP v2 in JTAG mode into dormant state.
+    pub async fn jtag_to_dormant(&mut self) {
+        // At least five clocks with TMS high reach Test-Logic-Reset first.
+        self.send_bits(&[true; 8]).await;
+        self.send_u32(JTAG_TO_DORMANT, 31).await;
+    }
+
+    /// Put an SWJ-DP v2 in SWD mode into dormant state.
+    pub async fn swd_to_dormant(&mut self) {
         self.line_reset(0).await;
-        self.jtag_to_swd().await;
+        self.send_u16(SWD_TO_DORMANT, 16).await;
+    }
+
+    /// Wake an SWJ-DP v2 from dormant state into SWD mode, a line reset has
+    /// to follow.
+    pub async fn dormant_to_swd(&mut self) {
+        self.send_bits(&[true; 8]).await;
+        for i in 0..4 {
+            self.send_u32((SELECTION_ALERT >> (32 * i)) as u32, 32)
+                .await;
+        }
+        self.send_bits(&[false; 4]).await;
+        self.send_u32(SWD_ACTIVATION_CODE.into(), 8).await;
+    }
+
+    /// Switch the target to SWD and read IDCODE, returning the wakeup
+    /// sequence that worked.
+    pub async fn reset(&mut self) -> Result<Wakeup, RequestError> {
+        trace!("Resetting SWD");
+        self.io.swclk_as_output();
+        self.io.swdio_as_output();
+        let wakeups: &[Wakeup] = match self.wakeup {
+            Some(Wakeup::Legacy) => &[Wakeup::Legacy],
+            Some(Wakeup::Dormant) => &[Wakeup::Dormant],
+            None => &[Wakeup::Legacy, Wakeup::Dormant],
+        };
+        let mut result = Err(RequestError::InvalidAck);
+        for &wakeup in wakeups {
+            match wakeup {
+                Wakeup::Legacy => {
+                    self.line_reset(0).await;
+                    self.jtag_to_swd().await;
+                }
+                Wakeup::Dormant => {
+                    // Reach dormant state from either SWD or JTAG mode.
+                    self.swd_to_dormant().await;
+                    self.jtag_to_dormant().await;
+                    self.dormant_to_swd().await;
+                }
+            }
+            self.line_reset(2).await;
+            if let Some(targetsel) = self.target {
+                self.write_targetsel(targetsel).await;
+            }
+            result = match self
+                .read_request(APnDP::DP, Idcode::A)
+                .await
+                .map(Idcode::from)
+            {
+                Ok(idcode) if idcode.present() => Ok(wakeup),
+                Ok(_) => Err(RequestError::InvalidAck),
+                Err(err) => Err(err),
+            };
+            if result.is_ok() {
+                break;
+            }
+            info!("{:?} wakeup failed: {:?}", wakeup, result);
+        }
+        result
+    }
+
+    /// Request debug and system power and wait until both are acknowledged.
+    pub async fn power_up(&mut self) -> Result<CtrlStat, RequestError> {
+        self.modify_dp_register::<CtrlStat>(|reg| {
+            reg.set_cdbgpwrupreq(true).set_csyspwrupreq(true)
+        })
+        .await?;
+        let start = Instant::now();
+        loop {
+            let ctrlstat = self.read_dp_register::<CtrlStat>().await?;
+            if ctrlstat.cdbgpwrupack() && ctrlstat.csyspwrupack() {
+                return Ok(ctrlstat);
+            }
+            if start.elapsed() >= POWER_UP_TIMEOUT {
+                info!("Power up not acknowledged: {:x?}", ctrlstat);
+                return Err(RequestError::PowerUp);
+            }
+        }
+    }
+
+    /// Pulse CTRL/STAT.CDBGRSTREQ. What it resets is implementation defined
+    /// and many targets ignore it, so a missing acknowledge is not an error.
+    pub async fn debug_reset(&mut self) -> Result<(), RequestError> {
+        self.modify_dp_register::<CtrlStat>(|reg| reg.set_cdbgrstreq(true))
+            .await?;
+        let start = Instant::now();
+        while !self.read_dp_register::<CtrlStat>().await?.cdbgrstack() {
+            if start.elapsed() >= POWER_UP_TIMEOUT {
+                info!("Debug reset not acknowledged");
+                break;
+            }
+        }
+        self.modify_dp_register::<CtrlStat>(|reg| reg.set_cdbgrstreq(false))
+            .await
+    }
+
+    /// Bring the link back after a target reset dropped it: wake the DP up
+    /// again, clear any sticky flags and power up the debug domains.
+    pub async fn reconnect(&mut self) -> Result<CtrlStat, RequestError> {
+        self.reset().await?;
+        let abort = Abort::default()
+            .set_orunerrclr(true)
+            .set_wderrclr(true)
+            .set_stkerrclr(true)
+            .set_stkcmpclr(true);
+        self.write_abort(abort).await?;
+        self.power_up().await
+    }
+
+    /// Select one target on a multi-drop bus (SWD protocol version 2).
+    ///
+    /// `targetid` is the TARGETID value of the target, its TREVISION field is
+    /// replaced by `instance`. Once it answered, the target stays selected
+    /// across `reset` until `clear_target`. A target that does not answer
+    /// leaves the previous selection in place.
+    pub async fn select_target(
+        &mut self,
+        targetid: u32,
+        instance: u8,
+    ) -> Result<Idcode, RequestError> {
+        let targetsel = TargetSel::from(targetid | 1).set_tinstance(instance);
         self.line_reset(2).await;
+        self.write_targetsel(targetsel).await;
+        // The first request after TARGETSEL must be a DPIDR read.
+        let idcode: Idcode = self.read_request(APnDP::DP, Idcode::A).await?.into();
+        if !idcode.present() {
+            return Err(RequestError::InvalidAck);
+        }
+        self.target = Some(targetsel);
+        Ok(idcode)
+    }
+
+    /// Target selected with `select_target`, if any.
+    pub fn selected_target(&self) -> Option<TargetSel> {
+        self.target
+    }
+
+    /// Forget the target selected with `select_target`, so `reset` talks to
+    /// a single-drop DP again.
+    pub fn clear_target(&mut self) {
+        self.target = None;
+    }
+
+    /// Write TARGETSEL, which no target acknowledges since all of them
+    /// could be listening.
+    pub async fn write_targetsel(&mut self, targetsel: TargetSel) {
+        // Every target has its own SELECT.
+        self.select = None;
+        self.select1 = None;
+        self.send_request(APnDP::DP, RnW::Write, TargetSel::A).await;
+        self.turnaround_target().await;
+        self.recv_bits(&mut [false; 3]).await;
+        self.turnaround_host().await;
+        let value: u32 = targetsel.into();
+        self.send_u32(value, 32).await;
+        self.send_bits(&[value.count_ones() & 1 == 1]).await;
     }
 }
 
@@ -206,26 +516,43 @@ pub enum RequestError {
     InvalidAck,
     #[error("Parity Error")]
     ParityError,
+    #[error("SWD Fault ack with sticky flags set: {0:?}")]
+    Sticky(CtrlStat),
+    #[error("Debug power up not acknowledged")]
+    PowerUp,
+    #[error("Batch of {0} requests with room for {1} values")]
+    BatchLength(usize, usize),
+    #[error("Unaligned access to {0:#010x}")]
+    Unaligned(u32),
 }
 
+/// Error codes of the network protocol, 0x00 is success.
 impl From<RequestError> for u8 {
     fn from(value: RequestError) -> Self {
         match value {
-            RequestError::Timeout => 0x00,
+            RequestError::Timeout => 0x07,
             RequestError::Fault => 0x01,
             RequestError::InvalidAck => 0x02,
             RequestError::ParityError => 0x03,
+            RequestError::Sticky(_) => 0x04,
+            RequestError::PowerUp => 0x05,
+            RequestError::BatchLength(..) => 0x08,
+            RequestError::Unaligned(_) => 0x09,
         }
     }
 }
 
-impl Swd<'_> {
+impl<Io: SwdIo> Swd<Io> {
     pub async fn send_request(&mut self, apndp: APnDP, rnw: RnW, a: [bool; 2]) {
         let apndp: bool = apndp.into();
         let rnw: bool = rnw.into();
         let parity = apndp ^ rnw ^ a[0] ^ a[1];
         let request = [true, apndp, rnw, a[0], a[1], parity, false, true];
-        self.send_bits(&request).await;
+        let request = request
+            .iter()
+            .enumerate()
+            .fold(0, |n, (i, b)| n | ((*b as u32) << (i as u32)));
+        self.send_u32(request, 8).await;
     }
 
     pub async fn recv_ack(&mut self) -> Result<Ack, InvalidAck> {
@@ -234,52 +561,114 @@ impl Swd<'_> {
         ack.try_into()
     }
 
+    /// Count a WAIT ack, aborting the stalled transaction through DAPABORT
+    /// once the retry budget or the deadline is used up.
+    async fn wait_ack(&mut self, retries: &mut usize, start: Instant) -> Result<(), RequestError> {
+        *retries += 1;
+        if *retries < self.wait_retries && start.elapsed() < self.wait_timeout {
+            trace!("Got Ack::Wait retrying {}", retries);
+            return Ok(());
+        }
+        info!(
+            "Gave up after {} WAIT acks in {} us, aborting",
+            retries,
+            start.elapsed().as_micros()
+        );
+        if let Err(err) = self.write_abort(Abort::default().set_dapabort(true)).await {
+            info!("DAPABORT failed: {:?}", err);
+        }
+        Err(RequestError::Timeout)
+    }
+
+    /// Write ABORT without WAIT handling, the DP accepts it even while an AP
+    /// transaction is stalled.
+    pub async fn write_abort(&mut self, abort: Abort) -> Result<(), RequestError> {
+        // The aborted transaction may have been a SELECT write.
+        self.select = None;
+        self.select1 = None;
+        self.send_request(APnDP::DP, RnW::Write, Abort::A).await;
+        self.turnaround_target().await;
+        let ack = self.recv_ack().await;
+        self.turnaround_host().await;
+        let result = match ack {
+            Ok(Ack::Ok) => Ok(()),
+            Ok(Ack::Wait) => Err(RequestError::Timeout),
+            Ok(Ack::Fault) => Err(RequestError::Fault),
+            Err(InvalidAck) => return Err(RequestError::InvalidAck),
+        };
+        if result.is_ok() || self.line.data_phase {
+            let value: u32 = abort.into();
+            self.send_u32(value, 32).await;
+            self.send_bits(&[value.count_ones() & 1 == 1]).await;
+        }
+        result
+    }
+
     pub async fn read_request(&mut self, apndp: APnDP, a: [bool; 2]) -> Result<u32, RequestError> {
-        let mut retries = 10;
+        let start = Instant::now();
+        let mut retries = 0;
         loop {
             self.send_request(apndp, RnW::Read, a).await;
             self.turnaround_target().await;
-            match self
-                .recv_ack()
-                .await
-                .map_err(|_| RequestError::InvalidAck)?
-            {
+            let Ok(ack) = self.recv_ack().await else {
+                self.turnaround_host().await;
+                return Err(RequestError::InvalidAck);
+            };
+            match ack {
                 Ack::Ok => break,
-                Ack::Wait => {
+                ack @ (Ack::Wait | Ack::Fault) if self.line.data_phase => {
+                    self.recv_u32(32).await;
+                    self.recv_bits(&mut [false]).await;
                     self.turnaround_host().await;
-                    retries -= 1;
-                    if retries == 0 {
-                        return Err(RequestError::Timeout);
+                    // With overrun detection the WAIT already set STICKYORUN.
+                    if ack == Ack::Wait && !self.overrun_detect {
+                        self.wait_ack(&mut retries, start).await?;
+                        continue;
                     }
-                    info!("Got Ack::Wait retrying {}", retries);
+                    self.select = None;
+                    return Err(RequestError::Fault);
+                }
+                Ack::Wait => {
+                    self.turnaround_host().await;
+                    self.wait_ack(&mut retries, start).await?;
                     continue;
                 }
                 Ack::Fault => {
                     self.turnaround_host().await;
+                    self.select = None;
                     return Err(RequestError::Fault);
                 }
             }
         }
 
-        let mut bits = [false; 33];
-        self.recv_bits(&mut bits).await;
-        let parity = bits[..32].iter().fold(false, |p, b| p ^ b);
-        if parity != bits[32] {
+        let value = self.recv_u32(32).await;
+        let mut parity = [false];
+        self.recv_bits(&mut parity).await;
+        // Take the line back before reporting anything, or the next request
+        // goes out undriven.
+        self.turnaround_host().await;
+        if parity[0] != (value.count_ones() & 1 == 1) {
             return Err(RequestError::ParityError);
         }
 
-        self.turnaround_host().await;
-
-        Ok(bits[..u32::BITS as usize]
-            .iter()
-            .enumerate()
-            .fold(0, |n, (i, b)| n | ((*b as u32) << (i as u32))))
+        Ok(value)
     }
 
     pub async fn read_dp_register<Reg: dp::ReadRegister>(&mut self) -> Result<Reg, RequestError> {
-        let reg = self.read_request(APnDP::DP, Reg::A).await.map(Into::into);
-        trace!("Read DP register {:?} {:x?}", Reg::A, reg);
-        reg
+        let result = async {
+            if let Some(bank) = Reg::BANK {
+                self.select_dp_bank(bank).await?;
+            }
+            let reg = self.read_request(APnDP::DP, Reg::A).await.map(Into::into);
+            trace!("Read DP register {:?} {:?} {:x?}", Reg::A, Reg::BANK, reg);
+            if Reg::BANK.is_some_and(|bank| bank != 0) {
+                let restored = self.select_dp_bank(0).await;
+                return reg.and_then(|reg| restored.map(|()| reg));
+            }
+            reg
+        }
+        .await;
+        self.recover(result).await
     }
 
     pub async fn write_request(
@@ -288,23 +677,36 @@ impl Swd<'_> {
         a: [bool; 2],
         value: u32,
     ) -> Result<(), RequestError> {
-        let mut retries = 10;
+        let writes_select = apndp == APnDP::DP && a == Select::A;
+        if writes_select {
+            self.select = None;
+        }
+        let start = Instant::now();
+        let mut retries = 0;
         loop {
             self.send_request(apndp, RnW::Write, a).await;
             self.turnaround_target().await;
             match self.recv_ack().await {
                 Ok(Ack::Ok) => break,
-                Ok(Ack::Wait) => {
+                Ok(ack @ (Ack::Wait | Ack::Fault)) if self.line.data_phase => {
                     self.turnaround_host().await;
-                    retries -= 1;
-                    if retries == 0 {
-                        return Err(RequestError::Timeout);
+                    self.send_u32(0, 32).await;
+                    self.send_bits(&[false]).await;
+                    if ack == Ack::Wait && !self.overrun_detect {
+                        self.wait_ack(&mut retries, start).await?;
+                        continue;
                     }
-                    trace!("Got Ack::Wait retrying {}", retries);
+                    self.select = None;
+                    return Err(RequestError::Fault);
+                }
+                Ok(Ack::Wait) => {
+                    self.turnaround_host().await;
+                    self.wait_ack(&mut retries, start).await?;
                     continue;
                 }
                 Ok(Ack::Fault) => {
                     self.turnaround_host().await;
+                    self.select = None;
                     return Err(RequestError::Fault);
                 }
                 Err(InvalidAck) => {
@@ -315,20 +717,102 @@ impl Swd<'_> {
         }
         self.turnaround_host().await;
 
-        let bits: [bool; 32] = from_fn(|i| (value >> i) & 1 == 1);
-        self.send_bits(&bits).await;
-        let parity = bits.iter().fold(false, |p, b| p ^ b);
+        self.send_u32(value, 32).await;
+        let parity = value.count_ones() & 1 == 1;
         self.send_bits(&[parity]).await;
 
+        if writes_select {
+            self.select = Some(value.into());
+        }
+        if apndp == APnDP::DP && a == CtrlStat::A {
+            self.track_banked_write(value);
+        }
+        Ok(())
+    }
+
+    /// Follow writes to CTRL/STAT and DLCR, which change the wire protocol
+    /// from the next transaction on, and to SELECT1.
+    fn track_banked_write(&mut self, value: u32) {
+        // Banked registers are only selected for the access, bank 0 otherwise.
+        match self.select.map_or(0, |select| select.dpbanksel()) {
+            0 => {
+                self.overrun_detect = CtrlStat::from(value).orundetect();
+                self.line.data_phase = self.overrun_detect;
+            }
+            1 => self.line.turnaround = Dlcr::from(value).turnround() + 1,
+            5 => self.select1 = Some(value),
+            _ => {}
+        }
+    }
+
+    /// Set the turnaround period of the DP and the host, 1 to 4 cycles.
+    pub async fn set_turnaround(&mut self, cycles: u8) -> Result<(), RequestError> {
+        let turnround = cycles.clamp(1, 4) - 1;
+        self.modify_dp_register::<Dlcr>(|reg| reg.set_turnround(turnround))
+            .await
+    }
+
+    /// Write SELECT unless it already holds `select`.
+    pub async fn select(&mut self, select: Select) -> Result<(), RequestError> {
+        if self.select != Some(select) {
+            trace!("Selecting {:x?}", select);
+            self.write_request(APnDP::DP, Select::A, select.into())
+                .await?;
+        }
         Ok(())
     }
 
+    /// Point SELECT, and SELECT1 for ADIv6, at the register `addr` of `ap`.
+    pub async fn select_ap(&mut self, ap: ApAddress, addr: u16) -> Result<(), RequestError> {
+        match ap {
+            ApAddress::Adiv5(apsel) => {
+                self.select(
+                    Select::default()
+                        .set_apsel(apsel)
+                        .set_apbanksel((addr >> 4) as u8),
+                )
+                .await
+            }
+            ApAddress::Adiv6(base) => {
+                let address = base + u64::from(addr);
+                let high = (address >> 32) as u32;
+                if self.select1 != Some(high) {
+                    self.write_dp_register(Select1::from(high)).await?;
+                }
+                self.select(Select::default().set_addr((address as u32) >> 4))
+                    .await
+            }
+        }
+    }
+
+    /// Select the DP register bank at address 0x4, keeping the AP selection.
+    pub async fn select_dp_bank(&mut self, bank: u8) -> Result<(), RequestError> {
+        let select = self.select.unwrap_or_default().set_dpbanksel(bank);
+        self.select(select).await
+    }
+
     pub async fn write_dp_register<Reg: dp::WriteRegister>(
         &mut self,
         reg: Reg,
     ) -> Result<(), RequestError> {
-        trace!("Writing DP register {:?} {:x?}", Reg::A, &reg);
-        self.write_request(APnDP::DP, Reg::A, reg.into()).await
+        trace!(
+            "Writing DP register {:?} {:?} {:x?}",
+            Reg::A,
+            Reg::BANK,
+            reg
+        );
+        let result = async {
+            if let Some(bank) = Reg::BANK {
+                self.select_dp_bank(bank).await?;
+            }
+            let result = self.write_request(APnDP::DP, Reg::A, reg.into()).await;
+            if Reg::BANK.is_some_and(|bank| bank != 0) {
+                return result.and(self.select_dp_bank(0).await);
+            }
+            result
+        }
+        .await;
+        self.recover(result).await
     }
 
     pub async fn modify_dp_register<Reg: dp::ReadRegister + dp::WriteRegister>(
@@ -341,41 +825,302 @@ impl Swd<'_> {
         Ok(())
     }
 
-    pub async fn read_ap(&mut self, ap: u8, addr: u8) -> Result<u32, RequestError> {
-        self.write_dp_register(Select::default().set_apsel(ap).set_apbanksel(addr >> 4))
+    /// Read CTRL/STAT after a fault ack and clear the sticky flags through
+    /// ABORT, returning CTRL/STAT as it was before clearing.
+    ///
+    /// Uses raw requests, as every other DP access faults until the flags
+    /// are cleared.
+    pub async fn clear_sticky(&mut self) -> Result<CtrlStat, RequestError> {
+        let ctrlstat: CtrlStat = self.read_request(APnDP::DP, CtrlStat::A).await?.into();
+        let abort = Abort::default()
+            .set_orunerrclr(ctrlstat.stickyorun())
+            .set_wderrclr(ctrlstat.wdataerr())
+            .set_stkerrclr(ctrlstat.stickyerr())
+            .set_stkcmpclr(ctrlstat.stickycmp());
+        self.write_request(APnDP::DP, Abort::A, abort.into())
             .await?;
-        let value = self.read_selected_ap(addr).await?;
-        trace!("Reading AP register {:02x}:{:02x}: {:08x}", ap, addr, value);
+        info!("Cleared sticky flags: {:x?}", ctrlstat);
+        Ok(ctrlstat)
+    }
+
+    /// Clean up after a failed access so the session can continue. A fault
+    /// ack turns into `RequestError::Sticky` with the flags cleared, a
+    /// missing ack is followed by `resync`.
+    async fn recover<T>(&mut self, result: Result<T, RequestError>) -> Result<T, RequestError> {
+        match result {
+            Err(RequestError::Fault) => Err(RequestError::Sticky(self.clear_sticky().await?)),
+            Err(RequestError::InvalidAck) => {
+                self.resync().await?;
+                Err(RequestError::InvalidAck)
+            }
+            result => result,
+        }
+    }
+
+    /// Line reset after a request the DP did not answer, which may have
+    /// locked it out, and read DPIDR as the first request after it.
+    async fn resync(&mut self) -> Result<(), RequestError> {
+        self.line_reset(2).await;
+        if let Some(targetsel) = self.target {
+            self.write_targetsel(targetsel).await;
+        }
+        self.read_request(APnDP::DP, Idcode::A).await?;
+        Ok(())
+    }
+
+    pub async fn read_ap(
+        &mut self,
+        ap: impl Into<ApAddress>,
+        addr: u16,
+    ) -> Result<u32, RequestError> {
+        let ap = ap.into();
+        let result = async {
+            self.select_ap(ap, addr).await?;
+            self.read_selected_ap(addr as u8).await
+        }
+        .await;
+        let value = self.recover(result).await?;
+        trace!("Reading AP register {:x?}:{:03x}: {:08x}", ap, addr, value);
         Ok(value)
     }
 
     pub async fn read_selected_ap(&mut self, addr: u8) -> Result<u32, RequestError> {
         self.read_request(APnDP::AP, [addr & 0x04 == 0x04, addr & 0x08 == 0x08])
             .await?;
-        let value = self.read_dp_register::<RdBuff>().await?.data();
+        let value = self.read_request(APnDP::DP, RdBuff::A).await?;
         Ok(value)
     }
 
+    /// Read the same AP register `values.len()` times.
+    ///
+    /// AP reads are posted, so each read returns the result of the one before
+    /// it and only the last value has to be fetched from RDBUFF.
+    pub async fn read_ap_repeated(
+        &mut self,
+        ap: impl Into<ApAddress>,
+        addr: u16,
+        values: &mut [u32],
+    ) -> Result<(), RequestError> {
+        let ap = ap.into();
+        let result = async {
+            self.select_ap(ap, addr).await?;
+            self.read_selected_ap_repeated(addr as u8, values).await
+        }
+        .await;
+        self.recover(result).await
+    }
+
+    pub async fn read_selected_ap_repeated(
+        &mut self,
+        addr: u8,
+        values: &mut [u32],
+    ) -> Result<(), RequestError> {
+        if values.is_empty() {
+            return Ok(());
+        }
+        let a = [addr & 0x04 == 0x04, addr & 0x08 == 0x08];
+        if self.overrun_detect {
+            self.stream_request(APnDP::AP, RnW::Read, a, 0).await?;
+            for i in 1..values.len() {
+                values[i - 1] = self.stream_request(APnDP::AP, RnW::Read, a, 0).await?;
+            }
+            values[values.len() - 1] = self
+                .stream_request(APnDP::DP, RnW::Read, RdBuff::A, 0)
+                .await?;
+            return self.check_overrun().await;
+        }
+        self.read_request(APnDP::AP, a).await?;
+        for i in 1..values.len() {
+            values[i - 1] = self.read_request(APnDP::AP, a).await?;
+        }
+        values[values.len() - 1] = self.read_request(APnDP::DP, RdBuff::A).await?;
+        trace!("Read AP register {:02x} {} times", addr, values.len());
+        Ok(())
+    }
+
+    /// Write the same AP register once per value.
+    pub async fn write_ap_repeated(
+        &mut self,
+        ap: impl Into<ApAddress>,
+        addr: u16,
+        values: &[u32],
+    ) -> Result<(), RequestError> {
+        let ap = ap.into();
+        let result = async {
+            self.select_ap(ap, addr).await?;
+            self.write_selected_ap_repeated(addr as u8, values).await
+        }
+        .await;
+        self.recover(result).await
+    }
+
+    pub async fn write_selected_ap_repeated(
+        &mut self,
+        addr: u8,
+        values: &[u32],
+    ) -> Result<(), RequestError> {
+        let a = [addr & 0x04 == 0x04, addr & 0x08 == 0x08];
+        if self.overrun_detect {
+            for &value in values {
+                self.stream_request(APnDP::AP, RnW::Write, a, value).await?;
+            }
+            return self.check_overrun().await;
+        }
+        for &value in values {
+            self.write_request(APnDP::AP, a, value).await?;
+        }
+        Ok(())
+    }
+
+    /// Enable or disable overrun detection in CTRL/STAT.
+    ///
+    /// With overrun detection on, repeated AP accesses are streamed without
+    /// looking at the individual acks. A WAIT or FAULT anywhere in the batch
+    /// sets STICKYORUN, which is checked once at the end of the batch.
+    pub async fn set_overrun_detect(&mut self, enable: bool) -> Result<(), RequestError> {
+        self.modify_dp_register::<CtrlStat>(|reg| reg.set_orundetect(enable))
+            .await
+    }
+
+    /// One transaction in overrun detect mode, where the data phase always
+    /// follows the ack and WAIT or FAULT acks are left to STICKYORUN.
+    async fn stream_request(
+        &mut self,
+        apndp: APnDP,
+        rnw: RnW,
+        a: [bool; 2],
+        value: u32,
+    ) -> Result<u32, RequestError> {
+        self.send_request(apndp, rnw, a).await;
+        self.turnaround_target().await;
+        let ack = self.recv_ack().await;
+        let value = match rnw {
+            RnW::Read => {
+                let value = self.recv_u32(32).await;
+                let mut parity = [false];
+                self.recv_bits(&mut parity).await;
+                self.turnaround_host().await;
+                if ack == Ok(Ack::Ok) && parity[0] != (value.count_ones() & 1 == 1) {
+                    return Err(RequestError::ParityError);
+                }
+                value
+            }
+            RnW::Write => {
+                self.turnaround_host().await;
+                self.send_u32(value, 32).await;
+                self.send_bits(&[value.count_ones() & 1 == 1]).await;
+                value
+            }
+        };
+        match ack {
+            Ok(_) => Ok(value),
+            Err(InvalidAck) => Err(RequestError::InvalidAck),
+        }
+    }
+
+    /// Check CTRL/STAT at the end of a streamed batch.
+    async fn check_overrun(&mut self) -> Result<(), RequestError> {
+        let ctrlstat: CtrlStat = self.read_request(APnDP::DP, CtrlStat::A).await?.into();
+        if ctrlstat.stickyorun() || ctrlstat.stickyerr() || ctrlstat.wdataerr() {
+            self.select = None;
+            return Err(RequestError::Fault);
+        }
+        Ok(())
+    }
+
+    /// Read a list of DP registers and registers of `ap` into `values`,
+    /// which needs one entry per request, using posted AP reads.
+    ///
+    /// The result of an AP read is collected by the next AP read, or from
+    /// RDBUFF before the next DP read, before SELECT moves to another bank
+    /// and at the end of the batch. With overrun detection on, the AP reads
+    /// are streamed and CTRL/STAT is checked once at the end.
+    pub async fn read_batch(
+        &mut self,
+        ap: impl Into<ApAddress>,
+        requests: &[(APnDP, u16)],
+        values: &mut [u32],
+    ) -> Result<(), RequestError> {
+        if requests.len() != values.len() {
+            return Err(RequestError::BatchLength(requests.len(), values.len()));
+        }
+        let ap = ap.into();
+        let result = self.read_selected_batch(ap, requests, values).await;
+        self.recover(result).await
+    }
+
+    async fn read_selected_batch(
+        &mut self,
+        ap: ApAddress,
+        requests: &[(APnDP, u16)],
+        values: &mut [u32],
+    ) -> Result<(), RequestError> {
+        let mut pending: Option<usize> = None;
+        for (i, &(apndp, addr)) in requests.iter().enumerate() {
+            let a = [addr & 0x04 == 0x04, addr & 0x08 == 0x08];
+            match apndp {
+                APnDP::AP => {
+                    if let Some(j) = pending.filter(|&j| requests[j].1 >> 4 != addr >> 4) {
+                        values[j] = self.posted_read(APnDP::DP, RdBuff::A).await?;
+                        pending = None;
+                    }
+                    self.select_ap(ap, addr).await?;
+                    let value = self.posted_read(APnDP::AP, a).await?;
+                    if let Some(j) = pending.replace(i) {
+                        values[j] = value;
+                    }
+                }
+                APnDP::DP => {
+                    if let Some(j) = pending.take() {
+                        values[j] = self.posted_read(APnDP::DP, RdBuff::A).await?;
+                    }
+                    values[i] = self.read_request(APnDP::DP, a).await?;
+                }
+            }
+        }
+        if let Some(j) = pending {
+            values[j] = self.posted_read(APnDP::DP, RdBuff::A).await?;
+        }
+        if self.overrun_detect {
+            self.check_overrun().await?;
+        }
+        Ok(())
+    }
+
+    /// A read that is streamed in overrun detect mode.
+    async fn posted_read(&mut self, apndp: APnDP, a: [bool; 2]) -> Result<u32, RequestError> {
+        if self.overrun_detect {
+            self.stream_request(apndp, RnW::Read, a, 0).await
+        } else {
+            self.read_request(apndp, a).await
+        }
+    }
+
     pub async fn read_ap_register<Reg: ap::ReadRegister>(
         &mut self,
-        ap: u8,
+        ap: impl Into<ApAddress>,
     ) -> Result<Reg, RequestError> {
-        let value = self.read_ap(ap, Reg::ADDRESS).await.map(Into::into)?;
-        trace!(
-            "Reading AP Register {:02}:{:02x}: {:?}",
-            ap,
-            Reg::ADDRESS,
-            value
-        );
+        let ap = ap.into();
+        let addr = ap.register::<Reg>();
+        let value = self.read_ap(ap, addr).await.map(Into::into)?;
+        trace!("Reading AP Register {:x?}:{:03x}: {:?}", ap, addr, value);
         Ok(value)
     }
 
-    pub async fn write_ap(&mut self, ap: u8, addr: u8, value: u32) -> Result<(), RequestError> {
-        trace!("Writing AP register {:02x}:{:02x}: {:08x}", ap, addr, value);
-        self.write_dp_register(Select::default().set_apsel(ap).set_apbanksel(addr >> 4))
-            .await?;
-        self.write_selected_ap(addr, value).await?;
-        Ok(())
+    pub async fn write_ap(
+        &mut self,
+        ap: impl Into<ApAddress>,
+        addr: u16,
+        value: u32,
+    ) -> Result<(), RequestError> {
+        let ap = ap.into();
+        trace!("Writing AP register {:x?}:{:03x}: {:08x}", ap, addr, value);
+        let result = async {
+            self.select_ap(ap, addr).await?;
+            self.write_selected_ap(addr as u8, value).await
+        }
+        .await;
+        self.recover(result).await
     }
 
     pub async fn write_selected_ap(&mut self, addr: u8, value: u32) -> Result<(), RequestError> {
@@ -386,26 +1131,413 @@ impl Swd<'_> {
 
     pub async fn write_ap_register<Reg: ap::WriteRegister>(
         &mut self,
-        ap: u8,
+        ap: impl Into<ApAddress>,
         reg: Reg,
     ) -> Result<(), RequestError> {
-        trace!(
-            "Writing AP register {:02x}:{:02x}: {:?}",
-            ap,
-            Reg::ADDRESS,
-            reg
-        );
-        self.write_ap(ap, Reg::ADDRESS, reg.into()).await
+        let ap = ap.into();
+        let addr = ap.register::<Reg>();
+        trace!("Writing AP register {:x?}:{:03x}: {:?}", ap, addr, reg);
+        self.write_ap(ap, addr, reg.into()).await
     }
 
     pub async fn modify_ap_register<Reg: ap::ReadRegister + ap::WriteRegister>(
         &mut self,
-        ap: u8,
+        ap: impl Into<ApAddress>,
         f: impl FnOnce(Reg) -> Reg,
     ) -> Result<(), RequestError> {
+        let ap = ap.into();
         let old_reg = self.read_ap_register(ap).await?;
         let new_reg = f(old_reg);
         self.write_ap_register(ap, new_reg).await?;
         Ok(())
     }
 }
+
+#[cfg(all(test, feature = "sim"))]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+    use crate::sim::{block_on, swd, Faults, MultiDrop, SimIo, Target, IDCODE};
+
+    const TARGETID: u32 = 0x0100_2927;
+
+    #[test]
+    fn reset_reads_idcode() {
+        let mut swd = swd();
+        block_on(async {
+            assert_eq!(swd.reset().await, Ok(Wakeup::Legacy));
+            let idcode = swd.read_dp_register::<Idcode>().await.unwrap();
+            assert_eq!(u32::from(idcode), IDCODE);
+        });
+    }
+
+    #[test]
+    fn dormant_wakeup() {
+        let mut swd = Swd::new(SimIo::new(Target::new(IDCODE)).dormant());
+        block_on(async {
+            swd.set_wakeup(Some(Wakeup::Legacy));
+            assert!(swd.reset().await.is_err());
+            swd.set_wakeup(None);
+            assert_eq!(swd.reset().await, Ok(Wakeup::Dormant));
+            let idcode = swd.read_dp_register::<Idcode>().await.unwrap();
+            assert_eq!(u32::from(idcode), IDCODE);
+        });
+    }
+
+    #[test]
+    fn dp_registers() {
+        let mut swd = swd();
+        block_on(async {
+            swd.reset().await.unwrap();
+            let ctrlstat = swd.power_up().await.unwrap();
+            assert!(ctrlstat.cdbgpwrupack() && ctrlstat.csyspwrupack());
+            swd.set_turnaround(2).await.unwrap();
+            assert_eq!(swd.line_config().turnaround, 2);
+            assert_eq!(swd.read_dp_register::<Dlcr>().await.unwrap().turnround(), 1);
+            let ctrlstat = swd.read_dp_register::<CtrlStat>().await.unwrap();
+            assert!(ctrlstat.cdbgpwrupack());
+        });
+        assert_eq!(swd.io.target.select.dpbanksel(), 0);
+    }
+
+    #[test]
+    fn ap_registers() {
+        let mut swd = swd();
+        block_on(async {
+            swd.reset().await.unwrap();
+            assert_eq!(swd.read_ap(0, 0xfc).await, Ok(0x24770011));
+            assert_eq!(swd.read_ap(0, 0xf8).await, Ok(0xe00ff003));
+            swd.write_ap(0, 0x04, 0x2000_0000).await.unwrap();
+            assert_eq!(swd.read_ap(0, 0x04).await, Ok(0x2000_0000));
+        });
+        assert_eq!(swd.io.target.aps[0].tar, 0x2000_0000);
+    }
+
+    #[test]
+    fn error_codes() {
+        let codes = [
+            RequestError::Timeout,
+            RequestError::Fault,
+            RequestError::InvalidAck,
+            RequestError::ParityError,
+            RequestError::Sticky(CtrlStat::default()),
+            RequestError::PowerUp,
+            RequestError::BatchLength(1, 0),
+            RequestError::Unaligned(1),
+        ]
+        .map(u8::from);
+        for (i, code) in codes.iter().enumerate() {
+            assert_ne!(*code, 0x00);
+            assert!(!codes[i + 1..].contains(code));
+        }
+    }
+
+    #[test]
+    fn frequency_is_clamped() {
+        let mut swd = swd();
+        block_on(async {
+            let start = Instant::now();
+            let hz = swd.set_frequency(0).await;
+            assert!(start.elapsed() < Duration::from_millis(100));
+            assert!(hz > 0 && hz <= MIN_FREQUENCY);
+            let start = Instant::now();
+            swd.set_frequency(u32::MAX).await;
+            assert!(start.elapsed() < Duration::from_millis(100));
+            assert_eq!(swd.clock.half_period_ns(), 500_000_000 / MAX_FREQUENCY);
+        });
+    }
+
+    #[test]
+    fn wait_timeout() {
+        let mut swd = swd();
+        block_on(async {
+            swd.reset().await.unwrap();
+            swd.io.target.faults.wait = 2 * DEFAULT_WAIT_RETRIES;
+            assert_eq!(
+                swd.read_dp_register::<Idcode>().await,
+                Err(RequestError::Timeout)
+            );
+            // DAPABORT ended the stalled transaction.
+            assert_eq!(swd.io.target.faults.wait, 0);
+            assert!(swd.read_dp_register::<Idcode>().await.is_ok());
+        });
+    }
+
+    #[test]
+    fn parity_error() {
+        let mut swd = swd();
+        block_on(async {
+            swd.reset().await.unwrap();
+            swd.io.target.faults.parity = 1;
+            assert_eq!(
+                swd.read_dp_register::<Idcode>().await,
+                Err(RequestError::ParityError)
+            );
+            let idcode = swd.read_dp_register::<Idcode>().await.unwrap();
+            assert_eq!(u32::from(idcode), IDCODE);
+        });
+    }
+
+    #[test]
+    fn sticky_recovery() {
+        let mut swd = swd();
+        block_on(async {
+            swd.reset().await.unwrap();
+            swd.io.target.faults.fault = 1;
+            match swd.read_ap(0, 0xfc).await {
+                Err(RequestError::Sticky(ctrlstat)) => assert!(ctrlstat.stickyerr()),
+                result => panic!("expected sticky error, got {:x?}", result),
+            }
+            assert!(!swd.io.target.ctrlstat.stickyerr());
+            assert_eq!(swd.read_ap(0, 0xfc).await, Ok(0x24770011));
+        });
+    }
+
+    #[test]
+    fn select_cache() {
+        let mut swd = swd();
+        block_on(async {
+            swd.reset().await.unwrap();
+            swd.read_ap(0, 0xfc).await.unwrap();
+            let writes = swd.io.target.select_writes;
+            swd.read_ap(0, 0xf8).await.unwrap();
+            assert_eq!(swd.io.target.select_writes, writes);
+            swd.read_ap(0, 0x04).await.unwrap();
+            assert_eq!(swd.io.target.select_writes, writes + 1);
+
+            swd.line_reset(2).await;
+            swd.read_request(APnDP::DP, Idcode::A).await.unwrap();
+            swd.read_ap(0, 0x04).await.unwrap();
+            assert_eq!(swd.io.target.select_writes, writes + 2);
+
+            swd.write_abort(Abort::default()).await.unwrap();
+            swd.read_ap(0, 0x04).await.unwrap();
+            assert_eq!(swd.io.target.select_writes, writes + 3);
+
+            swd.write_targetsel(TargetSel::default()).await;
+            swd.read_ap(0, 0x04).await.unwrap();
+            assert_eq!(swd.io.target.select_writes, writes + 4);
+            swd.read_ap(0, 0x04).await.unwrap();
+            assert_eq!(swd.io.target.select_writes, writes + 4);
+        });
+    }
+
+    #[test]
+    fn read_ap_repeated() {
+        let mut swd = swd();
+        block_on(async {
+            swd.reset().await.unwrap();
+            let mut values = [0; 4];
+            swd.read_ap_repeated(0, 0xfc, &mut values).await.unwrap();
+            assert_eq!(values, [0x24770011; 4]);
+
+            // SELECT is cached, so the fourth request is the last AP read.
+            swd.io.target.faults = Faults {
+                wait: 2,
+                skip: 3,
+                ..Faults::default()
+            };
+            values.fill(0);
+            swd.read_ap_repeated(0, 0xfc, &mut values).await.unwrap();
+            assert_eq!(values, [0x24770011; 4]);
+            assert_eq!(swd.io.target.faults, Faults::default());
+
+            swd.io.target.faults = Faults {
+                fault: 1,
+                skip: 2,
+                ..Faults::default()
+            };
+            match swd.read_ap_repeated(0, 0xfc, &mut values).await {
+                Err(RequestError::Sticky(ctrlstat)) => assert!(ctrlstat.stickyerr()),
+                result => panic!("expected sticky error, got {:x?}", result),
+            }
+            values.fill(0);
+            swd.read_ap_repeated(0, 0xfc, &mut values).await.unwrap();
+            assert_eq!(values, [0x24770011; 4]);
+        });
+    }
+
+    #[test]
+    fn overrun_detect() {
+        let mut swd = swd();
+        block_on(async {
+            swd.reset().await.unwrap();
+            swd.set_overrun_detect(true).await.unwrap();
+            let mut values = [0; 4];
+            swd.read_ap_repeated(0, 0xfc, &mut values).await.unwrap();
+            assert_eq!(values, [0x24770011; 4]);
+
+            swd.io.target.faults = Faults {
+                wait: 1,
+                skip: 2,
+                ..Faults::default()
+            };
+            match swd.read_ap_repeated(0, 0xfc, &mut values).await {
+                Err(RequestError::Sticky(ctrlstat)) => assert!(ctrlstat.stickyorun()),
+                result => panic!("expected sticky error, got {:x?}", result),
+            }
+            assert!(!swd.io.target.ctrlstat.stickyorun());
+            assert!(swd.io.target.ctrlstat.orundetect());
+            values.fill(0);
+            swd.read_ap_repeated(0, 0xfc, &mut values).await.unwrap();
+            assert_eq!(values, [0x24770011; 4]);
+        });
+    }
+
+    #[test]
+    fn read_batch() {
+        const REQUESTS: [(APnDP, u16); 5] = [
+            (APnDP::AP, 0xfc),
+            (APnDP::DP, 0x0),
+            (APnDP::AP, 0xf8),
+            (APnDP::AP, 0x04),
+            (APnDP::AP, 0xfc),
+        ];
+        const VALUES: [u32; 5] = [0x24770011, IDCODE, 0xe00ff003, 0x2000_0000, 0x24770011];
+
+        let mut swd = swd();
+        block_on(async {
+            swd.reset().await.unwrap();
+            swd.write_ap(0, 0x04, 0x2000_0000).await.unwrap();
+            let mut values = [0; 5];
+            swd.read_batch(0, &REQUESTS, &mut values).await.unwrap();
+            assert_eq!(values, VALUES);
+            assert_eq!(
+                swd.read_batch(0, &REQUESTS, &mut values[..4]).await,
+                Err(RequestError::BatchLength(5, 4))
+            );
+
+            swd.io.target.faults = Faults {
+                wait: 2,
+                skip: 3,
+                ..Faults::default()
+            };
+            values.fill(0);
+            swd.read_batch(0, &REQUESTS, &mut values).await.unwrap();
+            assert_eq!(values, VALUES);
+            assert_eq!(swd.io.target.faults, Faults::default());
+
+            swd.io.target.faults = Faults {
+                fault: 1,
+                skip: 3,
+                ..Faults::default()
+            };
+            match swd.read_batch(0, &REQUESTS, &mut values).await {
+                Err(RequestError::Sticky(ctrlstat)) => assert!(ctrlstat.stickyerr()),
+                result => panic!("expected sticky error, got {:x?}", result),
+            }
+
+            swd.set_overrun_detect(true).await.unwrap();
+            swd.io.target.faults = Faults {
+                wait: 1,
+                skip: 3,
+                ..Faults::default()
+            };
+            match swd.read_batch(0, &REQUESTS, &mut values).await {
+                Err(RequestError::Sticky(ctrlstat)) => assert!(ctrlstat.stickyorun()),
+                result => panic!("expected sticky error, got {:x?}", result),
+            }
+            values.fill(0);
+            swd.read_batch(0, &REQUESTS, &mut values).await.unwrap();
+            assert_eq!(values, VALUES);
+        });
+    }
+
+    #[test]
+    fn no_reply_recovery() {
+        let mut swd = swd();
+        block_on(async {
+            swd.reset().await.unwrap();
+            assert_eq!(swd.read_ap(0, 0xfc).await, Ok(0x24770011));
+            swd.io.target.faults.no_reply = 1;
+            assert_eq!(swd.read_ap(0, 0xfc).await, Err(RequestError::InvalidAck));
+            assert_eq!(swd.read_ap(0, 0xfc).await, Ok(0x24770011));
+        });
+    }
+
+    #[test]
+    fn no_reply() {
+        let mut swd = swd();
+        block_on(async {
+            swd.reset().await.unwrap();
+            swd.io.target.faults.no_reply = 1;
+            assert_eq!(
+                swd.read_dp_register::<Idcode>().await,
+                Err(RequestError::InvalidAck)
+            );
+            // The DP ignores everything after a protocol error until the
+            // line reset that followed it.
+            let idcode = swd.read_dp_register::<Idcode>().await.unwrap();
+            assert_eq!(u32::from(idcode), IDCODE);
+        });
+    }
+
+    #[test]
+    fn select_target() {
+        let bus = MultiDrop::new(vec![
+            Target::new(IDCODE).with_targetid(TARGETID, 0),
+            Target::new(IDCODE + 2).with_targetid(TARGETID, 1),
+        ]);
+        let mut swd = Swd::new(SimIo::new(bus));
+        block_on(async {
+            // Both targets answer and collide until one is selected.
+            assert!(swd.reset().await.is_err());
+            let idcode = swd.select_target(TARGETID, 1).await.unwrap();
+            assert_eq!(u32::from(idcode), IDCODE + 2);
+            let selected = swd.selected_target();
+            assert!(swd.select_target(TARGETID, 2).await.is_err());
+            assert_eq!(swd.selected_target(), selected);
+            swd.reset().await.unwrap();
+            let idcode = swd.read_dp_register::<Idcode>().await.unwrap();
+            assert_eq!(u32::from(idcode), IDCODE + 2);
+        });
+    }
+
+    #[test]
+    fn clear_target() {
+        let target = Target::new(IDCODE).with_targetid(TARGETID, 0);
+        let mut swd = Swd::new(SimIo::new(target));
+        block_on(async {
+            swd.reset().await.unwrap();
+            assert!(swd.select_target(TARGETID, 1).await.is_err());
+            assert_eq!(swd.selected_target(), None);
+            swd.reset().await.unwrap();
+            swd.select_target(TARGETID, 0).await.unwrap();
+            assert!(swd.selected_target().is_some());
+            swd.clear_target();
+            assert_eq!(swd.selected_target(), None);
+            swd.reset().await.unwrap();
+        });
+    }
+
+    #[test]
+    fn bank_restored_after_error() {
+        let mut swd = swd();
+        block_on(async {
+            swd.reset().await.unwrap();
+            swd.power_up().await.unwrap();
+            swd.io.target.faults.parity = 1;
+            assert_eq!(
+                swd.read_dp_register::<Dlcr>().await.err(),
+                Some(RequestError::ParityError)
+            );
+            assert_eq!(swd.io.target.select.dpbanksel(), 0);
+            assert!(swd
+                .read_dp_register::<CtrlStat>()
+                .await
+                .unwrap()
+                .cdbgpwrupack());
+            // CTRL/STAT selects bank 0 itself after a raw SELECT write.
+            let select = Select::default().set_dpbanksel(1);
+            swd.write_request(APnDP::DP, Select::A, select.into())
+                .await
+                .unwrap();
+            assert!(swd
+                .read_dp_register::<CtrlStat>()
+                .await
+                .unwrap()
+                .cdbgpwrupack());
+        });
+    }
+}